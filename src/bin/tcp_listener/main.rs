@@ -1,6 +1,5 @@
-use std::io::{self, ErrorKind, BufReader};
+use std::io::{self, BufReader};
 use std::net::{TcpListener, TcpStream};
-use std::sync::mpsc;
 use std::thread;
 
 use r_http::request::Request;
@@ -29,25 +28,20 @@ fn handle_client(stream: &TcpStream) {
     let mut reader = BufReader::new(stream);
 
     match Request::req_from_reader(&mut reader) {
-        Ok(req) => {
+        Ok(mut req) => {
             println!("--- Request from {:?} ---", peer);
             println!("Method: {}", req.method());
             println!("Target: {}", req.request_target());
             println!("Version: {}", req.http_version());
             println!("--- Headers ---");
-            match req.headers() {
-                Some(headers) => {
-                    for (k, v) in headers.iter() {
-                        println!("{} => {}", k, v);
-                    }
-                }
-                None => {}
+            for (k, v) in req.headers().iter() {
+                println!("{} => {}", k, v);
             }
             println!("--- Body ---");
-            if let Some(body) = req.body() {
-                println!("{}", String::from_utf8_lossy(body));
-            } else {
-                println!("No body");
+            match req.body().bytes() {
+                Ok(body) if !body.is_empty() => println!("{}", String::from_utf8_lossy(&body)),
+                Ok(_) => println!("No body"),
+                Err(e) => println!("Unreadable body: {}", e),
             }
         }
         Err(e) => {
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::io;
+
+use super::Cookie;
+use crate::crypto::{self, base64_decode, base64_encode, constant_time_eq, hmac_sha256};
+
+/// A secret for signing and encrypting cookies. Separate signing and
+/// encryption keys are derived from it, so one secret serves both.
+#[derive(Clone)]
+pub struct Key {
+    signing: [u8; 32],
+    encryption: [u8; 32],
+}
+
+impl Key {
+    /// Panics if `secret` is shorter than 32 bytes.
+    pub fn from_secret(secret: &[u8]) -> Key {
+        assert!(secret.len() >= 32, "Cookie key secrets must be at least 32 bytes long");
+        Key {
+            signing: hmac_sha256(secret, &[b"r_http cookie signing"]),
+            encryption: hmac_sha256(secret, &[b"r_http cookie encryption"]),
+        }
+    }
+
+    /// A key from a fresh random secret. Cookies made with it do not survive
+    /// a restart.
+    pub fn generate() -> io::Result<Key> {
+        let mut secret = [0u8; 32];
+        crypto::random_bytes(&mut secret)?;
+        Ok(Key::from_secret(&secret))
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// The keys cookies are signed and encrypted with. New cookies use the
+/// first key; all of them are tried when reading, so a retired key can be
+/// kept until the cookies made with it have expired.
+#[derive(Clone, Debug)]
+pub struct Keys {
+    keys: Vec<Key>,
+}
+
+impl Keys {
+    pub fn new(primary: Key) -> Keys {
+        Keys { keys: vec![primary] }
+    }
+
+    /// Adds a key that is still accepted but no longer used for new cookies.
+    pub fn with_previous(mut self, key: Key) -> Keys {
+        self.keys.push(key);
+        self
+    }
+
+    /// A cookie holding `value` followed by an HMAC-SHA256 of its name and
+    /// value. The value stays readable by the client; use `encrypt` to hide
+    /// it. Panics on values `Cookie::new` would refuse.
+    pub fn sign(&self, name: &str, value: &str) -> Cookie {
+        let mac = hmac_sha256(&self.keys[0].signing, &[name.as_bytes(), b"=", value.as_bytes()]);
+        Cookie::new(name, &format!("{}.{}", value, base64_encode(&mac)))
+    }
+
+    /// The value of a cookie made by `sign`, or `None` if it was not signed
+    /// by any of the keys or was altered.
+    pub fn verify(&self, name: &str, signed: &str) -> Option<String> {
+        let (value, mac) = signed.rsplit_once('.')?;
+        let mac = base64_decode(mac)?;
+        self.keys
+            .iter()
+            .any(|key| {
+                let expected = hmac_sha256(&key.signing, &[name.as_bytes(), b"=", value.as_bytes()]);
+                constant_time_eq(&expected, &mac)
+            })
+            .then(|| value.to_string())
+    }
+
+    /// A cookie holding `value` encrypted and authenticated with
+    /// ChaCha20-Poly1305. The name is bound in, so the value cannot be
+    /// moved to another cookie. `value` may hold any text.
+    pub fn encrypt(&self, name: &str, value: &str) -> io::Result<Cookie> {
+        let mut nonce = [0u8; 12];
+        crypto::random_bytes(&mut nonce)?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(crypto::seal(&self.keys[0].encryption, &nonce, name.as_bytes(), value.as_bytes()));
+        Ok(Cookie::new(name, &base64_encode(&sealed)))
+    }
+
+    /// The value of a cookie made by `encrypt`, or `None` if no key opens it.
+    pub fn decrypt(&self, name: &str, encrypted: &str) -> Option<String> {
+        let sealed = base64_decode(encrypted)?;
+        if sealed.len() < 12 {
+            return None;
+        }
+        let (nonce, sealed) = sealed.split_at(12);
+        let nonce: [u8; 12] = nonce.try_into().ok()?;
+        self.keys
+            .iter()
+            .find_map(|key| crypto::open(&key.encryption, &nonce, name.as_bytes(), sealed))
+            .and_then(|plain| String::from_utf8(plain).ok())
+    }
+}
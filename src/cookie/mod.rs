@@ -0,0 +1,222 @@
+mod keys;
+mod test;
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::headers::is_token_char;
+
+pub use keys::{Key, Keys};
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+/// A cookie to send with `Writer::set_cookie`, built up attribute by
+/// attribute (RFC 6265 section 4.1). Its `Display` form is the value of one
+/// `Set-Cookie` header line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    expires: Option<SystemTime>,
+    max_age: Option<Duration>,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    partitioned: bool,
+}
+
+fn is_cookie_octet(c: char) -> bool {
+    c.is_ascii_graphic() && !matches!(c, '"' | ',' | ';' | '\\')
+}
+
+fn is_attribute_value(s: &str) -> bool {
+    s.chars().all(|c| (c.is_ascii_graphic() || c == ' ') && c != ';')
+}
+
+impl Cookie {
+    /// Panics if `name` is not a token or `value` has characters a cookie
+    /// value cannot hold, such as spaces, commas or semicolons; encode such
+    /// values first.
+    pub fn new(name: &str, value: &str) -> Cookie {
+        assert!(
+            !name.is_empty() && name.chars().all(is_token_char),
+            "Invalid cookie name '{}'",
+            name
+        );
+        assert!(value.chars().all(is_cookie_octet), "Invalid value for cookie '{}'", name);
+        Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            expires: None,
+            max_age: None,
+            domain: None,
+            path: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            partitioned: false,
+        }
+    }
+
+    /// A cookie that tells the client to delete `name`. Give it the same
+    /// `path` and `domain` the cookie was set with.
+    pub fn removal(name: &str) -> Cookie {
+        Cookie::new(name, "").max_age(Duration::ZERO).expires(UNIX_EPOCH)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn expires(mut self, at: SystemTime) -> Self {
+        self.expires = Some(at);
+        self
+    }
+
+    /// Lifetime in whole seconds; takes precedence over `Expires` in clients
+    /// that support both.
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    pub fn domain(mut self, domain: &str) -> Self {
+        assert!(is_attribute_value(domain), "Invalid cookie domain '{}'", domain);
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        assert!(is_attribute_value(path), "Invalid cookie path '{}'", path);
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Asks for the cookie to be kept per top-level site (CHIPS).
+    pub fn partitioned(mut self, partitioned: bool) -> Self {
+        self.partitioned = partitioned;
+        self
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", http_date(expires))?;
+        }
+        if let Some(age) = self.max_age {
+            write!(f, "; Max-Age={}", age.as_secs())?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        // Clients drop SameSite=None and partitioned cookies that are not
+        // Secure, so those imply it.
+        if self.secure || self.same_site == Some(SameSite::None) || self.partitioned {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        if self.partitioned {
+            write!(f, "; Partitioned")?;
+        }
+        Ok(())
+    }
+}
+
+/// Name and value pairs of `Cookie` header values, in order. Pairs without
+/// `=` are skipped and double quotes around a value are removed; values are
+/// otherwise left as sent.
+pub fn parse<'a>(values: impl Iterator<Item = &'a str>) -> Vec<(&'a str, &'a str)> {
+    values
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let name = name.trim();
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            (!name.is_empty()).then_some((name, value))
+        })
+        .collect()
+}
+
+/// Formats `time` as an IMF-fixdate (RFC 9110 section 5.6.7), e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`. Times before 1970 are clamped to it.
+pub fn http_date(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
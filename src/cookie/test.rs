@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::cookie::{Cookie, Key, Keys, SameSite, http_date};
+    use crate::request::Request;
+    use crate::response::Writer;
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(http_date(UNIX_EPOCH + Duration::from_secs(784_111_777)), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(http_date(UNIX_EPOCH + Duration::from_secs(1_709_210_096)), "Thu, 29 Feb 2024 12:34:56 GMT");
+    }
+
+    #[test]
+    fn test_set_cookie_attributes() {
+        let cookie = Cookie::new("id", "a3fWa")
+            .expires(UNIX_EPOCH + Duration::from_secs(1_792_480_080))
+            .max_age(Duration::from_secs(3600))
+            .domain("example.com")
+            .path("/app")
+            .secure(true)
+            .http_only(true)
+            .same_site(SameSite::Strict);
+        assert_eq!(
+            cookie.to_string(),
+            "id=a3fWa; Expires=Tue, 20 Oct 2026 07:08:00 GMT; Max-Age=3600; Domain=example.com; \
+             Path=/app; Secure; HttpOnly; SameSite=Strict"
+        );
+
+        assert_eq!(Cookie::new("a", "1").to_string(), "a=1");
+        assert_eq!(
+            Cookie::new("a", "1").same_site(SameSite::None).partitioned(true).to_string(),
+            "a=1; Secure; SameSite=None; Partitioned"
+        );
+        assert_eq!(
+            Cookie::removal("a").path("/").to_string(),
+            "a=; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0; Path=/"
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_cookie_value_panics() {
+        let _ = Cookie::new("a", "two words");
+    }
+
+    #[test]
+    fn test_request_cookies() {
+        let raw = b"GET / HTTP/1.1\r\nHost: a\r\nCookie: theme=dark; id=\"42\"; junk; theme=light\r\n\r\n";
+        let req = Request::req_from_reader(&mut BufReader::new(&raw[..])).unwrap();
+        assert_eq!(req.cookies(), vec![("theme", "dark"), ("id", "42"), ("theme", "light")]);
+        assert_eq!(req.cookie("theme"), Some("dark"));
+        assert_eq!(req.cookie("id"), Some("42"));
+        assert_eq!(req.cookie("missing"), None);
+    }
+
+    #[test]
+    fn test_one_header_line_per_cookie() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_cookie(&Cookie::new("a", "1").path("/"));
+        writer.set_cookie(&Cookie::new("b", "2").http_only(true));
+        writer.finish().unwrap();
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nSet-Cookie: a=1; Path=/\r\nSet-Cookie: b=2; HttpOnly\r\n"));
+    }
+
+    fn key(byte: u8) -> Key {
+        Key::from_secret(&[byte; 32])
+    }
+
+    #[test]
+    fn test_signed_cookies() {
+        let keys = Keys::new(key(1));
+        let cookie = keys.sign("user", "alice").path("/");
+        assert!(cookie.value().starts_with("alice."));
+        assert_eq!(keys.verify("user", cookie.value()), Some("alice".to_string()));
+
+        let forged = cookie.value().replacen("alice", "admin", 1);
+        assert_eq!(keys.verify("user", &forged), None);
+        assert_eq!(keys.verify("other", cookie.value()), None, "Name is part of the signature");
+        assert_eq!(keys.verify("user", "alice"), None);
+        assert_eq!(Keys::new(key(2)).verify("user", cookie.value()), None);
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let old = Keys::new(key(1));
+        let rotated = Keys::new(key(2)).with_previous(key(1));
+
+        let legacy = old.sign("id", "7");
+        assert_eq!(rotated.verify("id", legacy.value()), Some("7".to_string()));
+        let fresh = rotated.sign("id", "7");
+        assert_eq!(old.verify("id", fresh.value()), None, "New cookies use the new key");
+
+        let legacy = old.encrypt("id", "7").unwrap();
+        assert_eq!(rotated.decrypt("id", legacy.value()), Some("7".to_string()));
+    }
+
+    #[test]
+    fn test_encrypted_cookies() {
+        let keys = Keys::new(key(3));
+        let cookie = keys.encrypt("prefs", "theme=dark; lang=pt-BR").unwrap();
+        assert!(!cookie.value().contains("dark"));
+        assert_ne!(cookie.value(), keys.encrypt("prefs", "theme=dark; lang=pt-BR").unwrap().value());
+        assert_eq!(keys.decrypt("prefs", cookie.value()), Some("theme=dark; lang=pt-BR".to_string()));
+
+        assert_eq!(keys.decrypt("other", cookie.value()), None);
+        assert_eq!(Keys::new(key(4)).decrypt("prefs", cookie.value()), None);
+        let mut tampered = cookie.value().to_string();
+        tampered.replace_range(20..21, if &tampered[20..21] == "A" { "B" } else { "A" });
+        assert_eq!(keys.decrypt("prefs", &tampered), None);
+        assert_eq!(keys.decrypt("prefs", "short"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_short_secret_panics() {
+        let _ = Key::from_secret(b"too short");
+    }
+
+    #[test]
+    fn test_request_signed_and_private_cookies() {
+        let keys = Keys::new(key(5));
+        let signed = keys.sign("user", "alice");
+        let private = keys.encrypt("token", "s3cr3t").unwrap();
+        let raw = format!(
+            "GET / HTTP/1.1\r\nHost: a\r\nCookie: user=bob.forged; {}; {}\r\n\r\n",
+            signed, private
+        );
+        let req = Request::req_from_reader(&mut BufReader::new(raw.as_bytes())).unwrap();
+        assert_eq!(req.signed_cookie(&keys, "user"), Some("alice".to_string()));
+        assert_eq!(req.private_cookie(&keys, "token"), Some("s3cr3t".to_string()));
+        assert_eq!(req.private_cookie(&keys, "user"), None);
+    }
+}
@@ -0,0 +1,201 @@
+//! ChaCha20-Poly1305 authenticated encryption (RFC 8439).
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn le_words<const N: usize>(bytes: &[u8]) -> [u32; N] {
+    let mut words = [0u32; N];
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    words
+}
+
+pub(crate) fn chacha20_block(key: &[u8; 32], counter: u32, nonce: &[u8; 12]) -> [u8; 64] {
+    let key: [u32; 8] = le_words(key);
+    let nonce: [u32; 3] = le_words(nonce);
+    let mut state = [
+        0x61707865, 0x3320646e, 0x79622d32, 0x6b206574,
+        key[0], key[1], key[2], key[3], key[4], key[5], key[6], key[7],
+        counter, nonce[0], nonce[1], nonce[2],
+    ];
+    let initial = state;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; 64];
+    for (i, chunk) in out.chunks_exact_mut(4).enumerate() {
+        chunk.copy_from_slice(&state[i].wrapping_add(initial[i]).to_le_bytes());
+    }
+    out
+}
+
+/// XORs `data` with the key stream starting at block `counter`.
+fn chacha20_xor(key: &[u8; 32], counter: u32, nonce: &[u8; 12], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let stream = chacha20_block(key, counter.wrapping_add(i as u32), nonce);
+        for (b, s) in chunk.iter_mut().zip(stream) {
+            *b ^= s;
+        }
+    }
+}
+
+/// Poly1305 one-time authenticator, with 26-bit limbs.
+pub(crate) fn poly1305(key: &[u8; 32], msg: &[u8]) -> [u8; 16] {
+    let r: [u32; 4] = le_words(&key[..16]);
+    let r0 = r[0] & 0x3ffffff;
+    let r1 = ((r[0] >> 26) | (r[1] << 6)) & 0x3ffff03;
+    let r2 = ((r[1] >> 20) | (r[2] << 12)) & 0x3ffc0ff;
+    let r3 = ((r[2] >> 14) | (r[3] << 18)) & 0x3f03fff;
+    let r4 = (r[3] >> 8) & 0x00fffff;
+    let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+
+    let (mut h0, mut h1, mut h2, mut h3, mut h4) = (0u32, 0u32, 0u32, 0u32, 0u32);
+    for chunk in msg.chunks(16) {
+        let mut block = [0u8; 17];
+        block[..chunk.len()].copy_from_slice(chunk);
+        block[chunk.len()] = 1;
+        let t: [u32; 4] = le_words(&block[..16]);
+        h0 += t[0] & 0x3ffffff;
+        h1 += ((t[0] >> 26) | (t[1] << 6)) & 0x3ffffff;
+        h2 += ((t[1] >> 20) | (t[2] << 12)) & 0x3ffffff;
+        h3 += ((t[2] >> 14) | (t[3] << 18)) & 0x3ffffff;
+        h4 += (t[3] >> 8) | ((block[16] as u32) << 24);
+
+        let m = |a: u32, b: u32| a as u64 * b as u64;
+        let d0 = m(h0, r0) + m(h1, s4) + m(h2, s3) + m(h3, s2) + m(h4, s1);
+        let mut d1 = m(h0, r1) + m(h1, r0) + m(h2, s4) + m(h3, s3) + m(h4, s2);
+        let mut d2 = m(h0, r2) + m(h1, r1) + m(h2, r0) + m(h3, s4) + m(h4, s3);
+        let mut d3 = m(h0, r3) + m(h1, r2) + m(h2, r1) + m(h3, r0) + m(h4, s4);
+        let mut d4 = m(h0, r4) + m(h1, r3) + m(h2, r2) + m(h3, r1) + m(h4, r0);
+
+        let mut c = (d0 >> 26) as u32;
+        h0 = d0 as u32 & 0x3ffffff;
+        d1 += c as u64;
+        c = (d1 >> 26) as u32;
+        h1 = d1 as u32 & 0x3ffffff;
+        d2 += c as u64;
+        c = (d2 >> 26) as u32;
+        h2 = d2 as u32 & 0x3ffffff;
+        d3 += c as u64;
+        c = (d3 >> 26) as u32;
+        h3 = d3 as u32 & 0x3ffffff;
+        d4 += c as u64;
+        c = (d4 >> 26) as u32;
+        h4 = d4 as u32 & 0x3ffffff;
+        h0 += c * 5;
+        c = h0 >> 26;
+        h0 &= 0x3ffffff;
+        h1 += c;
+    }
+
+    // Fully carry h, then compute h + -p and pick it if it did not underflow.
+    let mut c = h1 >> 26;
+    h1 &= 0x3ffffff;
+    h2 += c;
+    c = h2 >> 26;
+    h2 &= 0x3ffffff;
+    h3 += c;
+    c = h3 >> 26;
+    h3 &= 0x3ffffff;
+    h4 += c;
+    c = h4 >> 26;
+    h4 &= 0x3ffffff;
+    h0 += c * 5;
+    c = h0 >> 26;
+    h0 &= 0x3ffffff;
+    h1 += c;
+
+    let mut g0 = h0.wrapping_add(5);
+    c = g0 >> 26;
+    g0 &= 0x3ffffff;
+    let mut g1 = h1.wrapping_add(c);
+    c = g1 >> 26;
+    g1 &= 0x3ffffff;
+    let mut g2 = h2.wrapping_add(c);
+    c = g2 >> 26;
+    g2 &= 0x3ffffff;
+    let mut g3 = h3.wrapping_add(c);
+    c = g3 >> 26;
+    g3 &= 0x3ffffff;
+    let g4 = h4.wrapping_add(c).wrapping_sub(1 << 26);
+
+    let mask = (g4 >> 31).wrapping_sub(1);
+    let (g0, g1, g2, g3, g4) = (g0 & mask, g1 & mask, g2 & mask, g3 & mask, g4 & mask);
+    let mask = !mask;
+    let h0 = (h0 & mask) | g0;
+    let h1 = (h1 & mask) | g1;
+    let h2 = (h2 & mask) | g2;
+    let h3 = (h3 & mask) | g3;
+    let h4 = (h4 & mask) | g4;
+
+    let h0 = h0 | (h1 << 26);
+    let h1 = (h1 >> 6) | (h2 << 20);
+    let h2 = (h2 >> 12) | (h3 << 14);
+    let h3 = (h3 >> 18) | (h4 << 8);
+
+    let pad: [u32; 4] = le_words(&key[16..]);
+    let mut f = h0 as u64 + pad[0] as u64;
+    let mut out = [0u8; 16];
+    out[0..4].copy_from_slice(&(f as u32).to_le_bytes());
+    f = h1 as u64 + pad[1] as u64 + (f >> 32);
+    out[4..8].copy_from_slice(&(f as u32).to_le_bytes());
+    f = h2 as u64 + pad[2] as u64 + (f >> 32);
+    out[8..12].copy_from_slice(&(f as u32).to_le_bytes());
+    f = h3 as u64 + pad[3] as u64 + (f >> 32);
+    out[12..16].copy_from_slice(&(f as u32).to_le_bytes());
+    out
+}
+
+fn tag(key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+    let block = chacha20_block(key, 0, nonce);
+    let mut otk = [0u8; 32];
+    otk.copy_from_slice(&block[..32]);
+
+    let pad = |len: usize| vec![0u8; (16 - len % 16) % 16];
+    let mut data = Vec::with_capacity(aad.len() + ciphertext.len() + 48);
+    data.extend_from_slice(aad);
+    data.extend_from_slice(&pad(aad.len()));
+    data.extend_from_slice(ciphertext);
+    data.extend_from_slice(&pad(ciphertext.len()));
+    data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    poly1305(&otk, &data)
+}
+
+/// Encrypts `plaintext`, returning the ciphertext followed by the 16-byte tag.
+pub(crate) fn seal(key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut out = plaintext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut out);
+    let tag = tag(key, nonce, aad, &out);
+    out.extend_from_slice(&tag);
+    out
+}
+
+/// Checks the tag and decrypts, returning `None` if anything was altered.
+pub(crate) fn open(key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    let split = sealed.len().checked_sub(16)?;
+    let (ciphertext, received) = sealed.split_at(split);
+    if !super::constant_time_eq(&tag(key, nonce, aad, ciphertext), received) {
+        return None;
+    }
+    let mut out = ciphertext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut out);
+    Some(out)
+}
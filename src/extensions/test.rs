@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::extensions::Extensions;
+
+    #[derive(Debug, PartialEq)]
+    struct Config {
+        name: &'static str,
+    }
+
+    #[test]
+    fn test_insert_and_get_by_type() {
+        let mut ext = Extensions::new();
+        ext.insert(Config { name: "app" });
+        ext.insert(42u32);
+
+        assert_eq!(ext.get::<Config>(), Some(&Config { name: "app" }));
+        assert_eq!(ext.get::<u32>(), Some(&42));
+        assert_eq!(ext.get::<u64>(), None);
+        assert_eq!(ext.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_replaces_same_type() {
+        let mut ext = Extensions::new();
+        ext.insert(1u8);
+        ext.insert(2u8);
+        assert_eq!(ext.get::<u8>(), Some(&2));
+        assert_eq!(ext.len(), 1);
+    }
+
+    #[test]
+    fn test_clones_share_values() {
+        let mut ext = Extensions::new();
+        ext.insert(Mutex::new(0));
+        let cloned = ext.clone();
+
+        *ext.get::<Mutex<i32>>().unwrap().lock().unwrap() += 1;
+        assert_eq!(*cloned.get::<Mutex<i32>>().unwrap().lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut ext = Extensions::new();
+        ext.insert("value");
+        assert!(ext.remove::<&str>());
+        assert!(!ext.contains::<&str>());
+        assert!(ext.is_empty());
+    }
+}
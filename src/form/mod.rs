@@ -0,0 +1,96 @@
+mod test;
+
+use std::str::FromStr;
+
+use crate::response::{HandlerError, StatusCode};
+use crate::uri::form_decode;
+
+/// Name and value pairs from a query string or an
+/// `application/x-www-form-urlencoded` body, in the order they were sent.
+/// A name may appear more than once, as in `?tag=a&tag=b`. Names are
+/// matched exactly.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Form {
+    pairs: Vec<(String, String)>,
+}
+
+fn bad_request(message: String) -> HandlerError {
+    HandlerError { status: StatusCode::BadRequest, message }
+}
+
+impl Form {
+    /// Parses `name=value` pairs separated by `&`, decoding `+` as a space and
+    /// `%XX` escapes. Empty pairs are skipped and a pair without `=` has an
+    /// empty value. Malformed escapes are answered with 400.
+    pub fn parse(input: &str) -> Result<Form, HandlerError> {
+        let mut pairs = Vec::new();
+        for pair in input.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s| form_decode(s).map_err(|e| bad_request(format!("Malformed form data: {}\n", e)));
+            pairs.push((decode(name)?, decode(value)?));
+        }
+        Ok(Form { pairs })
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// The first value of `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of `name`, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.pairs.iter().filter(move |(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Parses the first value of `name`, answering 400 when it is missing or
+    /// malformed.
+    pub fn get_as<T: FromStr>(&self, name: &str) -> Result<T, HandlerError> {
+        self.get_optional_as(name)?
+            .ok_or_else(|| bad_request(format!("Missing field '{}'\n", name)))
+    }
+
+    /// Like `get_as`, but a missing field is `None` rather than an error.
+    pub fn get_optional_as<T: FromStr>(&self, name: &str) -> Result<Option<T>, HandlerError> {
+        self.get(name).map(|raw| parse_value(name, raw)).transpose()
+    }
+
+    /// Parses every value of `name`, answering 400 if any is malformed.
+    pub fn get_all_as<T: FromStr>(&self, name: &str) -> Result<Vec<T>, HandlerError> {
+        self.get_all(name).map(|raw| parse_value(name, raw)).collect()
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, raw: &str) -> Result<T, HandlerError> {
+    raw.parse()
+        .map_err(|_| bad_request(format!("Invalid value '{}' for field '{}'\n", raw, name)))
+}
+
+/// Types that can be built from a `Form`, for use with `Request::query_as`
+/// and `Request::form_as`. Implementations usually read each field with
+/// `Form::get_as`, `get_optional_as` or `get_all_as`, so a missing or
+/// malformed field is answered with 400.
+pub trait FromForm: Sized {
+    fn from_form(form: &Form) -> Result<Self, HandlerError>;
+}
+
+impl FromForm for Form {
+    fn from_form(form: &Form) -> Result<Self, HandlerError> {
+        Ok(form.clone())
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::form::{Form, FromForm};
+    use crate::request::Request;
+    use crate::response::{HandlerError, StatusCode};
+
+    struct Search {
+        term: String,
+        page: u32,
+        tags: Vec<String>,
+    }
+
+    impl FromForm for Search {
+        fn from_form(form: &Form) -> Result<Self, HandlerError> {
+            Ok(Search {
+                term: form.get_as("q")?,
+                page: form.get_optional_as("page")?.unwrap_or(1),
+                tags: form.get_all_as("tag")?,
+            })
+        }
+    }
+
+    fn request(raw: &str) -> Request {
+        Request::req_from_reader(&mut BufReader::new(raw.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_pairs_keep_order_and_repeats() {
+        let form = Form::parse("page=2&tag=a&tag=b&&flag&empty=").unwrap();
+        let pairs: Vec<_> = form.iter().collect();
+        assert_eq!(pairs, vec![("page", "2"), ("tag", "a"), ("tag", "b"), ("flag", ""), ("empty", "")]);
+        assert_eq!(form.get("tag"), Some("a"));
+        assert_eq!(form.get_all("tag").collect::<Vec<_>>(), vec!["a", "b"]);
+        assert!(!form.contains("Tag"));
+        assert!(Form::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plus_and_percent_decoding() {
+        let form = Form::parse("name=Jane+Doe&city=S%C3%A3o%20Paulo&a%2Bb=1%2B1").unwrap();
+        assert_eq!(form.get("name"), Some("Jane Doe"));
+        assert_eq!(form.get("city"), Some("São Paulo"));
+        assert_eq!(form.get("a+b"), Some("1+1"));
+    }
+
+    #[test]
+    fn test_malformed_input_is_bad_request() {
+        for input in ["a=%", "a=%zz", "%FF=1"] {
+            assert_eq!(Form::parse(input).unwrap_err().status, StatusCode::BadRequest);
+        }
+    }
+
+    #[test]
+    fn test_typed_extraction_from_query() {
+        let req = request("GET /search?q=rust+http&tag=a&tag=b HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let search: Search = req.query_as().unwrap();
+        assert_eq!(search.term, "rust http");
+        assert_eq!(search.page, 1);
+        assert_eq!(search.tags, vec!["a", "b"]);
+
+        let req = request("GET /search?page=2 HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let err = req.query_as::<Search>().err().unwrap();
+        assert_eq!(err.status, StatusCode::BadRequest);
+        assert_eq!(err.message, "Missing field 'q'\n");
+
+        let req = request("GET /search?q=x&page=two HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let err = req.query_as::<Search>().err().unwrap();
+        assert_eq!(err.message, "Invalid value 'two' for field 'page'\n");
+    }
+
+    #[test]
+    fn test_form_body() {
+        let mut req = request(
+            "POST /search HTTP/1.1\r\nHost: localhost\r\n\
+             Content-Type: application/x-www-form-urlencoded; charset=utf-8\r\n\
+             Content-Length: 17\r\n\r\nq=a+b&page=3&tag=",
+        );
+        let search: Search = req.form_as().unwrap();
+        assert_eq!(search.term, "a b");
+        assert_eq!(search.page, 3);
+        assert_eq!(search.tags, vec![""]);
+    }
+
+    #[test]
+    fn test_form_requires_content_type() {
+        let mut req = request(
+            "POST /search HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+             Content-Length: 3\r\n\r\nq=a",
+        );
+        assert_eq!(req.form().unwrap_err().status, StatusCode::UnsupportedMediaType);
+    }
+}
@@ -1,23 +1,22 @@
-mod test;
+pub mod test;
 
 use core::str;
-use std::collections::HashMap;
-use std::io::{BufRead, Error, ErrorKind};
-use std::ops::Deref;
+use std::io::{BufRead, Error, ErrorKind, Read};
 
-#[derive(Debug)]
-pub struct Headers(HashMap<String, (String, String)>);
+use crate::request::{Limits, ParseError};
 
-impl Deref for Headers {
-    type Target = HashMap<String, (String, String)>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
+/// Header fields in the order they were received or set. Names are matched
+/// case-insensitively but keep the casing they were given. Each field line
+/// is kept on its own, so repeated fields such as `Set-Cookie` survive
+/// intact; `get_combined` gives the comma-joined view where that is wanted.
+#[derive(Clone, Debug, Default)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
 }
 
+// Content-Length is left out: repeated identical values are allowed and
+// checked when the body framing is worked out.
 const SINGLETON_HEADERS: &[&str] = &[
-    "content-length",
     "host",
     "authorization",
     "content-type",
@@ -26,105 +25,192 @@ const SINGLETON_HEADERS: &[&str] = &[
     "date",
 ];
 
-impl Headers {
-    fn read_as_bytes(reader: &mut dyn BufRead) -> std::io::Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        let mut temp = Vec::new();
-        loop {
-            let n = reader.read_until(b'\n', &mut temp)?;
-            if n == 0 {
-                return Err(Error::new(
-                    ErrorKind::UnexpectedEof,
-                    "Unexpected end of stream while reading line",
-                ));
-            }
-
-            buf.extend_from_slice(&temp);
+/// Reads up to and including the next CRLF, failing with `too_long` once
+/// more than `max` bytes have been read without finding one.
+pub(crate) fn read_line(reader: &mut dyn BufRead, max: usize, too_long: ParseError) -> Result<Vec<u8>, ParseError> {
+    let mut buf = Vec::new();
+    loop {
+        let budget = (max + 1).saturating_sub(buf.len()) as u64;
+        let n = reader.take(budget).read_until(b'\n', &mut buf)?;
+        if n == 0 {
+            return Err(ParseError::Io(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Unexpected end of stream while reading line",
+            )));
+        }
 
-            if buf.len() >= 2 && &buf[buf.len() - 2..] == b"\r\n" {
-                break;
-            }
+        if buf.ends_with(b"\r\n") {
+            break;
+        }
+        if buf.len() > max {
+            return Err(too_long);
         }
-        Ok(buf)
     }
+    Ok(buf)
+}
+
+/// Strips optional whitespace, which is only spaces and tabs
+/// (RFC 9110 section 5.6.3), unlike `str::trim`.
+pub(crate) fn trim_ows(s: &str) -> &str {
+    s.trim_matches([' ', '\t'])
+}
 
-    fn is_token_char(c: char) -> bool {
-        c.is_ascii_alphanumeric() || matches!(c,
-            '!' | '#' | '$' | '%' | '&' | '\'' | '*' | '+' |
-            '-' | '.' | '^' | '_' | '`' | '|' | '~'
-        )
+pub(crate) fn assert_valid_field(key: &str, value: &str) {
+    assert!(!key.is_empty() && key.chars().all(is_token_char), "Invalid header name '{}'", key);
+    assert!(!value.contains(['\r', '\n', '\0']), "Invalid value for header '{}'", key);
+}
+
+pub(crate) fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c,
+        '!' | '#' | '$' | '%' | '&' | '\'' | '*' | '+' |
+        '-' | '.' | '^' | '_' | '`' | '|' | '~'
+    )
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Headers::default()
     }
 
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The first value of `key`.
     pub fn get_value(&self, key: &str) -> Option<&str> {
-        self.0.get(&key.to_ascii_lowercase()).map(|(_, v)| v.as_str())
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of `key`, in order.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
     }
 
+    /// All values of `key` joined with ", ", the combined form RFC 9110
+    /// section 5.3 allows for list-based fields. Not meaningful for fields
+    /// like `Set-Cookie` whose values may themselves contain commas.
+    pub fn get_combined(&self, key: &str) -> Option<String> {
+        let values: Vec<&str> = self.get_all(key).collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join(", "))
+        }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.get_value(key).is_some()
+    }
+
+    /// The name of `key` as it was first received or set.
     pub fn original_name(&self, key: &str) -> Option<&str> {
-        self.0.get(&key.to_ascii_lowercase()).map(|(k, _)| k.as_str())
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(k, _)| k.as_str())
+    }
+
+    /// Sets `key` to `value`, replacing every existing value of it.
+    pub fn insert(&mut self, key: &str, value: &str) {
+        self.remove(key);
+        self.append(key, value);
+    }
+
+    /// Adds a value for `key` after any existing ones. Panics if `key` is not
+    /// a token or `value` holds CR, LF or NUL, which would let data copied
+    /// from a request split the message.
+    pub fn append(&mut self, key: &str, value: &str) {
+        assert_valid_field(key, value);
+        self.entries.push((key.to_string(), value.to_string()));
     }
 
-    pub fn parse_header_line(reader: &mut dyn BufRead) -> std::io::Result<Headers> {
-        let mut line = Self::read_as_bytes(reader)?;
-        if line == b"\r\n" {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Unexpected CRLF after request line (violates RFC 9112)",
-            ));
+    /// Removes every value of `key`, returning whether there was one.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        self.entries.len() != before
+    }
+
+    /// Name and value pairs in order, names in their original casing.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn parse_header_line(reader: &mut dyn BufRead) -> Result<Headers, ParseError> {
+        Self::parse_with(reader, &Limits::default())
+    }
+
+    /// Reads a header section, which must not be empty.
+    pub(crate) fn parse_with(reader: &mut dyn BufRead, limits: &Limits) -> Result<Headers, ParseError> {
+        let headers = Self::parse_fields(reader, limits)?;
+        if headers.is_empty() {
+            return Err(ParseError::InvalidHeader("Unexpected CRLF after request line (violates RFC 9112)"));
         }
+        Ok(headers)
+    }
 
-        let mut headers: HashMap<String, (String, String)> = HashMap::new();
-        let mut line_str;
+    /// Reads field lines up to and including the empty line that ends them.
+    pub(crate) fn parse_fields(reader: &mut dyn BufRead, limits: &Limits) -> Result<Headers, ParseError> {
+        let mut line = read_line(reader, limits.max_header_line, ParseError::HeaderTooLarge)?;
+        let mut count = 0;
+        let mut total = 0;
 
+        let mut headers = Headers::new();
+        let mut line_str;
         loop {
             if line == b"\r\n" { break; }
 
+            count += 1;
+            total += line.len();
+            if count > limits.max_headers || total > limits.max_header_bytes {
+                return Err(ParseError::HeaderTooLarge);
+            }
+
             line_str = str::from_utf8(&line[..line.len() - 2])
-                .map_err(|_| Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 in header line"))?;
+                .map_err(|_| ParseError::InvalidHeader("Invalid UTF-8 in header line"))?;
             if let Some((key, value)) = line_str.split_once(':') {
 
                 if key.chars().any(|c| c.is_ascii_whitespace()) {
-                    return Err(Error::new(
-                        std::io::ErrorKind::InvalidData,
+                    return Err(ParseError::InvalidHeader(
                         "Invalid header should not allow whitespace in field name",
                     ));
-                } else if key.chars().any(|c| !Self::is_token_char(c)) {
-                    return Err(Error::new(
-                        std::io::ErrorKind::InvalidData,
+                } else if key.chars().any(|c| !is_token_char(c)) {
+                    return Err(ParseError::InvalidHeader(
                         "Invalid header should not allow alphanumeric characters in field name",
                     ));
                 }
 
+                // A bare LF could end the line for a less strict peer and
+                // smuggle in a field this parser never saw, and other controls
+                // get treated as whitespace by some (RFC 9110 section 5.5).
+                if value.chars().any(|c| c.is_ascii_control() && c != '\t') {
+                    return Err(ParseError::InvalidHeader("Invalid control character in field value"));
+                }
+
                 let key_lower = key.to_ascii_lowercase();
-                let value_trimmed = value.trim();
+                let value_trimmed = trim_ows(value);
 
-                if headers.contains_key(&key_lower) && SINGLETON_HEADERS.contains(&key_lower.as_str()) {
-                    return Err(Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "Duplicate singleton header should cause error",
-                    ));
-                } else if headers.contains_key(&key_lower) && !SINGLETON_HEADERS.contains(&key_lower.as_str()) {
-                    println!("Warning: Duplicate header found: {}", key_lower);
-                    if let Some((_, existing_value)) = headers.get_mut(&key_lower) {
-                        existing_value.push_str(", ");
-                        existing_value.push_str(value_trimmed);
-                    }
-                } else {
-                    headers.
-                        insert(
-                            key_lower,
-                            (key.to_string(), value_trimmed.to_string()),
-                        );
+                if headers.contains(key) && SINGLETON_HEADERS.contains(&key_lower.as_str()) {
+                    return Err(ParseError::DuplicateHeader(key_lower));
                 }
+                headers.append(key, value_trimmed);
             } else {
-                return Err(Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Invalid header format",
-                ));
+                return Err(ParseError::InvalidHeader("Invalid header format"));
             }
 
-            line = Self::read_as_bytes(reader)?;
+            line = read_line(reader, limits.max_header_line, ParseError::HeaderTooLarge)?;
         }
 
-        Ok(Headers(headers))
+        Ok(headers)
     }
 }
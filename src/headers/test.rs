@@ -37,6 +37,7 @@ mod tests {
     use std::io::BufReader;
 
     use crate::headers::Headers;
+    use crate::request::ParseError;
 
     use super::*;
 
@@ -72,7 +73,7 @@ mod tests {
         let mut reader = std::io::BufReader::new(chunk_reader);
         let result = Headers::parse_header_line(&mut reader);
         assert!(
-            result.is_err(),
+            matches!(result, Err(ParseError::DuplicateHeader(ref name)) if name == "host"),
             "Duplicate singleton header should cause error"
         );
     }
@@ -85,9 +86,10 @@ mod tests {
         let mut reader = std::io::BufReader::new(chunk_reader);
         let result = Headers::parse_header_line(&mut reader).expect("Failed to parse headers");
         assert_eq!(
-            result.get_value("accept").unwrap(),
+            result.get_combined("accept").unwrap(),
             "text/html, application/json",
         );
+        assert_eq!(result.get_all("accept").collect::<Vec<_>>(), vec!["text/html", "application/json"]);
     }
 
     #[test]
@@ -126,5 +128,60 @@ mod tests {
             "Empty header value should be allowed and parsed as empty string"
         );
     }
-}
 
+    #[test]
+    fn test_iteration_keeps_order_and_original_names() {
+        let request = b"X-Zeta: 1\r\nHost: localhost\r\nx-alpha: 2\r\n\r\n";
+        let mut reader = BufReader::new(ChunkReader::new(request, 4));
+        let headers = Headers::parse_header_line(&mut reader).expect("Failed to parse headers");
+
+        let names: Vec<&str> = headers.iter().map(|(k, _)| k).collect();
+        assert_eq!(names, vec!["X-Zeta", "Host", "x-alpha"]);
+        assert_eq!(headers.original_name("x-zeta"), Some("X-Zeta"));
+        assert_eq!(headers.get_value("X-ALPHA"), Some("2"));
+    }
+
+    #[test]
+    fn test_insert_append_and_remove() {
+        let mut headers = Headers::new();
+        headers.append("Vary", "Accept");
+        headers.append("vary", "Origin");
+        headers.append("Content-Type", "text/plain");
+        assert_eq!(headers.get_all("VARY").collect::<Vec<_>>(), vec!["Accept", "Origin"]);
+        assert_eq!(headers.len(), 3);
+
+        headers.insert("Vary", "*");
+        assert_eq!(headers.get_all("vary").collect::<Vec<_>>(), vec!["*"]);
+
+        assert!(headers.remove("content-type"));
+        assert!(!headers.remove("content-type"));
+        assert!(!headers.contains("Content-Type"));
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn test_set_cookie_lines_are_not_joined() {
+        let request = b"Set-Cookie: a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT\r\nSet-Cookie: b=2\r\n\r\n";
+        let mut reader = BufReader::new(ChunkReader::new(request, 4));
+        let headers = Headers::parse_header_line(&mut reader).expect("Failed to parse headers");
+
+        assert_eq!(
+            headers.get_all("set-cookie").collect::<Vec<_>>(),
+            vec!["a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT", "b=2"],
+        );
+        assert_eq!(headers.get_value("set-cookie"), Some("a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT"));
+        assert_eq!(headers.len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_line_break_in_value_panics() {
+        Headers::new().insert("Location", "/next\r\nSet-Cookie: admin=1");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_name_panics() {
+        Headers::new().append("X Injected", "1");
+    }
+}
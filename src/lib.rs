@@ -1,3 +1,13 @@
+pub mod cookie;
+mod crypto;
+pub mod extensions;
+pub mod form;
+pub mod headers;
+pub mod middleware;
+pub mod multipart;
 pub mod request;
 pub mod server;
 pub mod response;
+pub mod router;
+pub mod session;
+pub mod uri;
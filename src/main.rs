@@ -1,11 +1,6 @@
-pub mod headers;
-pub mod request;
-pub mod response;
-pub mod server;
-
-use crate::request::Request;
+use r_http::request::Request;
+use r_http::request::test::ChunkReader;
 use std::io::BufReader;
-use crate::request::test::ChunkReader;
 
 fn main() {
     // Example usage of the Request struct
@@ -0,0 +1,73 @@
+mod test;
+
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use crate::request::Request;
+use crate::response::{HandlerError, Writer};
+use crate::server::{Handler, Service};
+
+/// Cross-cutting behavior wrapped around a handler.
+///
+/// A middleware receives the request before the handler does and may change
+/// it, then calls `next.run` to continue down the chain. Because the `Writer`
+/// buffers the response until the server finishes it, the status, headers and
+/// body set further down can still be inspected and changed once `next.run`
+/// returns. Not calling `next.run` short-circuits the chain: the middleware's
+/// own response is sent and the handler never runs.
+pub trait Middleware: Send + Sync + 'static {
+    fn handle(&self, req: Request, res: &mut Writer<TcpStream>, next: Next<'_>) -> Result<(), HandlerError>;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(Request, &mut Writer<TcpStream>, Next<'_>) -> Result<(), HandlerError> + Send + Sync + 'static,
+{
+    fn handle(&self, req: Request, res: &mut Writer<TcpStream>, next: Next<'_>) -> Result<(), HandlerError> {
+        self(req, res, next)
+    }
+}
+
+/// The remainder of a middleware chain, ending with the handler.
+pub struct Next<'a> {
+    layers: &'a [Arc<dyn Middleware>],
+    handler: &'a dyn Service,
+}
+
+impl Next<'_> {
+    pub fn run(self, req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        match self.layers.split_first() {
+            Some((layer, rest)) => layer.handle(req, res, Next { layers: rest, handler: self.handler }),
+            None => self.handler.call(req, res),
+        }
+    }
+}
+
+/// A handler together with the middleware wrapped around it.
+///
+/// Layers run in the order they are added: the first layer sees the request
+/// first and the response last.
+pub struct Chain {
+    layers: Vec<Arc<dyn Middleware>>,
+    handler: Handler,
+}
+
+impl Chain {
+    pub fn new(handler: impl Service) -> Self {
+        Chain {
+            layers: Vec::new(),
+            handler: Arc::new(handler),
+        }
+    }
+
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.layers.push(Arc::new(middleware));
+        self
+    }
+}
+
+impl Service for Chain {
+    fn call(&self, req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        Next { layers: &self.layers, handler: self.handler.as_ref() }.run(req, res)
+    }
+}
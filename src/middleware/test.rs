@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::middleware::{Chain, Next};
+    use crate::request::Request;
+    use crate::response::{HandlerError, StatusCode, Writer};
+    use crate::server::Server;
+    use crate::server::test::roundtrip;
+
+    fn hello(req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        res.set_status(StatusCode::Ok);
+        let trace = req.header("X-Trace").unwrap_or("").to_string();
+        res.set_header("X-Trace", &format!("{}handler;", trace));
+        let _ = res.write_body(b"hello");
+        Ok(())
+    }
+
+    fn tracer(name: &'static str) -> impl Fn(Request, &mut Writer<TcpStream>, Next<'_>) -> Result<(), HandlerError> + Send + Sync + 'static {
+        move |req: Request, res: &mut Writer<TcpStream>, next: Next<'_>| {
+            next.run(req, res)?;
+            let trace = res.header("X-Trace").unwrap_or("").to_string();
+            res.set_header("X-Trace", &format!("{}{};", trace, name));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_layers_unwind_in_reverse_order() {
+        let app = Chain::new(hello).layer(tracer("outer")).layer(tracer("inner"));
+        let server = Server::start(0, app).expect("Failed to start server");
+
+        let res = roundtrip(&server, b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.contains("\r\nX-Trace: handler;inner;outer;\r\n"), "{}", res);
+    }
+
+    #[test]
+    fn test_middleware_modifies_request_and_response_body() {
+        let app = Chain::new(hello).layer(|mut req: Request, res: &mut Writer<TcpStream>, next: Next<'_>| {
+            req.extensions_mut().insert("seen");
+            assert_eq!(req.extensions().get::<&str>(), Some(&"seen"));
+            next.run(req, res)?;
+            let upper = res.body().to_ascii_uppercase();
+            res.set_body(upper);
+            res.set_status(StatusCode::NotFound);
+            Ok(())
+        });
+        let server = Server::start(0, app).expect("Failed to start server");
+
+        let res = roundtrip(&server, b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", res);
+        assert!(res.contains("\r\nContent-Length: 5\r\n"));
+        assert!(res.ends_with("\r\n\r\nHELLO"));
+    }
+
+    #[test]
+    fn test_middleware_can_short_circuit() {
+        let reached = Arc::new(AtomicBool::new(false));
+        let flag = reached.clone();
+        let handler = move |req: Request, res: &mut Writer<TcpStream>| {
+            flag.store(true, Ordering::SeqCst);
+            hello(req, res)
+        };
+        let app = Chain::new(handler).layer(|req: Request, res: &mut Writer<TcpStream>, next: Next<'_>| {
+            if req.header("Authorization").is_none() {
+                res.set_status(StatusCode::BadRequest);
+                let _ = res.write_body(b"denied");
+                return Ok(());
+            }
+            next.run(req, res)
+        });
+        let server = Server::start(0, app).expect("Failed to start server");
+
+        let res = roundtrip(&server, b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(res.ends_with("denied"));
+        assert!(!reached.load(Ordering::SeqCst), "Handler should not run");
+    }
+
+    #[test]
+    fn test_middleware_sees_handler_errors() {
+        let failing = |_req: Request, _res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            Err(HandlerError { status: StatusCode::InternalServerError, message: "boom".to_string() })
+        };
+        let app = Chain::new(failing).layer(|req: Request, res: &mut Writer<TcpStream>, next: Next<'_>| {
+            next.run(req, res).or_else(|e| {
+                res.set_status(StatusCode::ServiceUnavailable);
+                let _ = res.write_body(format!("recovered: {}", e.message).as_bytes());
+                Ok(())
+            })
+        });
+        let server = Server::start(0, app).expect("Failed to start server");
+
+        let res = roundtrip(&server, b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(res.ends_with("recovered: boom"));
+    }
+}
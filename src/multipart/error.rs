@@ -0,0 +1,94 @@
+use std::fmt;
+use std::io;
+
+use crate::request::ParseError;
+use crate::response::{HandlerError, StatusCode};
+
+/// Why a multipart body could not be read. Each variant maps to the status
+/// code a handler should answer with through `MultipartError::status`.
+#[derive(Debug)]
+pub enum MultipartError {
+    /// The request is not `multipart/form-data`.
+    NotMultipart,
+    /// The `Content-Type` has no usable `boundary` parameter.
+    InvalidBoundary(&'static str),
+    /// The body does not follow the multipart syntax.
+    Malformed(&'static str),
+    /// A part's header section is over the limit.
+    HeadersTooLarge,
+    /// A single part's body is over the limit.
+    PartTooLarge(String),
+    /// The whole multipart body is over the limit.
+    BodyTooLarge,
+    TooManyParts,
+    /// Reading the request body failed.
+    Read(ParseError),
+    /// Writing a part to a temporary file failed.
+    Spill(io::Error),
+}
+
+impl MultipartError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            MultipartError::NotMultipart => StatusCode::UnsupportedMediaType,
+            MultipartError::HeadersTooLarge
+            | MultipartError::PartTooLarge(_)
+            | MultipartError::BodyTooLarge
+            | MultipartError::TooManyParts => StatusCode::ContentTooLarge,
+            MultipartError::Read(e) => e.status(),
+            MultipartError::Spill(_) => StatusCode::InternalServerError,
+            _ => StatusCode::BadRequest,
+        }
+    }
+
+    /// Recovers a `MultipartError` that was passed through an `io::Error`,
+    /// as happens when reading a `Part`.
+    pub(crate) fn from_io(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<MultipartError>()) {
+            return *e.into_inner().unwrap().downcast::<MultipartError>().unwrap();
+        }
+        MultipartError::Read(ParseError::from_io(e))
+    }
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultipartError::NotMultipart => write!(f, "Expected a multipart/form-data body"),
+            MultipartError::InvalidBoundary(reason) => write!(f, "Invalid multipart boundary: {}", reason),
+            MultipartError::Malformed(reason) => write!(f, "Malformed multipart body: {}", reason),
+            MultipartError::HeadersTooLarge => write!(f, "Multipart part headers are too large"),
+            MultipartError::PartTooLarge(name) => write!(f, "Multipart part '{}' is too large", name),
+            MultipartError::BodyTooLarge => write!(f, "Multipart body is too large"),
+            MultipartError::TooManyParts => write!(f, "Too many multipart parts"),
+            MultipartError::Read(e) => write!(f, "{}", e),
+            MultipartError::Spill(e) => write!(f, "Failed to store multipart part: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MultipartError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MultipartError::Read(e) => Some(e),
+            MultipartError::Spill(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<MultipartError> for io::Error {
+    fn from(e: MultipartError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Lets handlers use `?` on multipart reads.
+impl From<MultipartError> for HandlerError {
+    fn from(e: MultipartError) -> Self {
+        HandlerError {
+            status: e.status(),
+            message: format!("{}\n", e),
+        }
+    }
+}
@@ -0,0 +1,417 @@
+mod error;
+mod temp;
+mod test;
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+use crate::headers::Headers;
+use crate::request;
+use crate::uri::percent_decode;
+
+pub use error::MultipartError;
+pub use temp::TempFile;
+
+type Result<T> = std::result::Result<T, MultipartError>;
+
+/// Size caps for a multipart body. The request's own body limit still
+/// applies on top of these.
+#[derive(Clone, Debug)]
+pub struct MultipartLimits {
+    /// Most parts in one body.
+    pub max_parts: usize,
+    /// Largest header section of a single part.
+    pub max_part_headers: usize,
+    /// Largest body of a single part.
+    pub max_part_size: usize,
+    /// Largest multipart body as a whole, boundaries and headers included.
+    pub max_total_size: usize,
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        MultipartLimits {
+            max_parts: 100,
+            max_part_headers: 8 * 1024,
+            max_part_size: 10 * 1024 * 1024,
+            max_total_size: 10 * 1024 * 1024,
+        }
+    }
+}
+
+/// Returns the `boundary` parameter of a `multipart/form-data` content type.
+pub fn boundary(content_type: &str) -> Result<String> {
+    let mut params = split_params(content_type).into_iter();
+    let media_type = params.next().unwrap_or_default();
+    if !media_type.eq_ignore_ascii_case("multipart/form-data") {
+        return Err(MultipartError::NotMultipart);
+    }
+
+    let boundary = params
+        .filter_map(|p| param(&p).filter(|(name, _)| name.eq_ignore_ascii_case("boundary")).map(|(_, v)| v))
+        .next()
+        .ok_or(MultipartError::InvalidBoundary("missing"))?;
+    // RFC 2046 section 5.1.1.
+    if boundary.is_empty() || boundary.len() > 70 {
+        return Err(MultipartError::InvalidBoundary("must be 1 to 70 characters long"));
+    }
+    if !boundary.bytes().all(|b| b.is_ascii_graphic() || b == b' ') || boundary.ends_with(' ') {
+        return Err(MultipartError::InvalidBoundary("contains invalid characters"));
+    }
+    Ok(boundary)
+}
+
+/// Splits a header value on `;`, leaving semicolons inside quoted strings
+/// alone.
+fn split_params(value: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    params.push(current.trim().to_string());
+    params
+}
+
+/// Splits `name=value`, unquoting a quoted-string value.
+fn param(param: &str) -> Option<(String, String)> {
+    let (name, value) = param.split_once('=')?;
+    let value = value.trim();
+    let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => {
+            let mut unquoted = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                unquoted.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+            }
+            unquoted
+        }
+        None => value.to_string(),
+    };
+    Some((name.trim().to_ascii_lowercase(), value))
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    /// Reading the preamble or a part's body, up to the next delimiter.
+    Body,
+    /// Just past a delimiter; either a part or the end follows.
+    Boundary,
+    Done,
+}
+
+/// Reads the parts of a `multipart/form-data` body one at a time. Part
+/// bodies are streamed from the underlying reader, so a file upload never
+/// has to fit in memory.
+pub struct Multipart<R: Read> {
+    reader: R,
+    /// `CRLF--boundary`; the CRLF belongs to the delimiter, not the part.
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    pos: usize,
+    state: State,
+    limits: MultipartLimits,
+    total: usize,
+    parts: usize,
+}
+
+impl<R: Read> Multipart<R> {
+    pub fn new(reader: R, boundary: &str, limits: MultipartLimits) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Multipart {
+            reader,
+            delimiter,
+            // The first delimiter may open the body without a CRLF before it.
+            buf: b"\r\n".to_vec(),
+            pos: 0,
+            state: State::Body,
+            limits,
+            total: 0,
+            parts: 0,
+        }
+    }
+
+    /// Moves to the next part, skipping whatever is left of the current
+    /// one. Returns `None` after the closing delimiter.
+    pub fn next_part(&mut self) -> Result<Option<Part<'_, R>>> {
+        let mut sink = [0u8; 8192];
+        while self.state == State::Body {
+            self.read_body(&mut sink)?;
+        }
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        self.fill_to(2)?;
+        if self.buf[self.pos..].starts_with(b"--") {
+            // Anything after the closing delimiter is an epilogue to ignore.
+            self.state = State::Done;
+            return Ok(None);
+        }
+
+        // Transport padding may follow the boundary (RFC 2046 section 5.1.1).
+        let line_end = self.find(b"\r\n", self.limits.max_part_headers)?;
+        if !self.buf[self.pos..line_end].iter().all(|b| matches!(b, b' ' | b'\t')) {
+            return Err(MultipartError::Malformed("Unexpected data after boundary"));
+        }
+        self.pos = line_end + 2;
+
+        self.parts += 1;
+        if self.parts > self.limits.max_parts {
+            return Err(MultipartError::TooManyParts);
+        }
+
+        let headers = self.read_headers()?;
+        let disposition = headers
+            .get_value("Content-Disposition")
+            .ok_or(MultipartError::Malformed("Part has no Content-Disposition"))?;
+        let mut params = split_params(disposition).into_iter();
+        if !params.next().is_some_and(|t| t.eq_ignore_ascii_case("form-data")) {
+            return Err(MultipartError::Malformed("Part is not form-data"));
+        }
+
+        let mut name = None;
+        let mut filename = None;
+        let mut filename_ext = None;
+        for (key, value) in params.filter_map(|p| param(&p)) {
+            match key.as_str() {
+                "name" => name = Some(value),
+                "filename" => filename = Some(value),
+                // RFC 5987 extended value, `charset'language'value`; only
+                // UTF-8 is accepted and the language is ignored.
+                "filename*" => {
+                    filename_ext = value
+                        .split_once('\'')
+                        .and_then(|(charset, rest)| Some((charset, rest.split_once('\'')?.1)))
+                        .filter(|(charset, _)| charset.eq_ignore_ascii_case("utf-8"))
+                        .and_then(|(_, encoded)| percent_decode(encoded).ok());
+                }
+                _ => {}
+            }
+        }
+        let name = name.ok_or(MultipartError::Malformed("Part has no name"))?;
+
+        self.state = State::Body;
+        Ok(Some(Part {
+            multipart: self,
+            headers,
+            name,
+            filename: filename_ext.or(filename),
+            len: 0,
+        }))
+    }
+
+    fn read_headers(&mut self) -> Result<Headers> {
+        self.fill_to(2)?;
+        if self.buf[self.pos..].starts_with(b"\r\n") {
+            self.pos += 2;
+            return Ok(Headers::new());
+        }
+
+        let end = self.find(b"\r\n\r\n", self.limits.max_part_headers)? + 4;
+        let limits = request::Limits {
+            max_header_line: self.limits.max_part_headers,
+            max_header_bytes: self.limits.max_part_headers,
+            ..Default::default()
+        };
+        let mut section = Cursor::new(&self.buf[self.pos..end]);
+        let headers = Headers::parse_fields(&mut section, &limits)
+            .map_err(|_| MultipartError::Malformed("Invalid part header"))?;
+        self.pos = end;
+        Ok(headers)
+    }
+
+    /// Copies body bytes up to the next delimiter into `out`. Returns 0 and
+    /// moves past the delimiter once it is reached.
+    fn read_body(&mut self, out: &mut [u8]) -> Result<usize> {
+        if self.state != State::Body || out.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let data = &self.buf[self.pos..];
+            let found = data.windows(self.delimiter.len()).position(|w| w == self.delimiter);
+            // Without a delimiter, hold back a tail that could be the start of one.
+            let ready = found.unwrap_or(data.len().saturating_sub(self.delimiter.len() - 1));
+
+            if found == Some(0) {
+                self.pos += self.delimiter.len();
+                self.state = State::Boundary;
+                return Ok(0);
+            }
+            if ready > 0 {
+                let n = ready.min(out.len());
+                out[..n].copy_from_slice(&data[..n]);
+                self.pos += n;
+                return Ok(n);
+            }
+            if self.fill()? == 0 {
+                return Err(MultipartError::Malformed("Body ended before the closing boundary"));
+            }
+        }
+    }
+
+    /// Reads more of the body into the buffer, returning how much was read.
+    fn fill(&mut self) -> Result<usize> {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+
+        let mut chunk = [0u8; 8192];
+        let n = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(MultipartError::from_io(e)),
+            }
+        };
+        self.total += n;
+        if self.total > self.limits.max_total_size {
+            return Err(MultipartError::BodyTooLarge);
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(n)
+    }
+
+    /// Makes sure at least `n` unread bytes are buffered.
+    fn fill_to(&mut self, n: usize) -> Result<()> {
+        while self.buf.len() - self.pos < n {
+            if self.fill()? == 0 {
+                return Err(MultipartError::Malformed("Body ended before the closing boundary"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds `needle` in the unread bytes within `limit` of them, reading
+    /// more as needed. Returns its absolute position in the buffer.
+    fn find(&mut self, needle: &[u8], limit: usize) -> Result<usize> {
+        loop {
+            let data = &self.buf[self.pos..];
+            if let Some(i) = data.windows(needle.len()).position(|w| w == needle) {
+                return Ok(self.pos + i);
+            }
+            if data.len() > limit {
+                return Err(MultipartError::HeadersTooLarge);
+            }
+            if self.fill()? == 0 {
+                return Err(MultipartError::Malformed("Body ended before the closing boundary"));
+            }
+        }
+    }
+}
+
+/// Where `Part::spool` left a part's body.
+#[derive(Debug)]
+pub enum PartData {
+    Memory(Vec<u8>),
+    /// A body larger than the spool threshold, rewound to its start.
+    File(TempFile),
+}
+
+/// One part of a multipart body. It reads its body straight from the
+/// request; whatever is not read is skipped by the next `next_part`.
+pub struct Part<'a, R: Read> {
+    multipart: &'a mut Multipart<R>,
+    headers: Headers,
+    name: String,
+    filename: Option<String>,
+    len: usize,
+}
+
+impl<R: Read> Part<'_, R> {
+    /// The `name` of the form field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The file name the client gave, for file fields. It comes straight
+    /// from the client, so never use it as a path as is.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get_value("Content-Type")
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    fn read_part(&mut self, out: &mut [u8]) -> Result<usize> {
+        let n = self.multipart.read_body(out)?;
+        self.len += n;
+        if self.len > self.multipart.limits.max_part_size {
+            return Err(MultipartError::PartTooLarge(self.name.clone()));
+        }
+        Ok(n)
+    }
+
+    /// Reads the rest of the body into memory.
+    pub fn to_bytes(&mut self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = self.read_part(&mut buf)?;
+            if n == 0 {
+                return Ok(bytes);
+            }
+            bytes.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    /// Reads the rest of the body as UTF-8 text.
+    pub fn text(&mut self) -> Result<String> {
+        String::from_utf8(self.to_bytes()?).map_err(|_| MultipartError::Malformed("Part is not valid UTF-8"))
+    }
+
+    /// Reads the rest of the body, keeping it in memory up to `threshold`
+    /// bytes and moving it to a temporary file beyond that.
+    pub fn spool(&mut self, threshold: usize) -> Result<PartData> {
+        let mut bytes = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = self.read_part(&mut buf)?;
+            if n == 0 {
+                return Ok(PartData::Memory(bytes));
+            }
+            bytes.extend_from_slice(&buf[..n]);
+            if bytes.len() > threshold {
+                break;
+            }
+        }
+
+        let mut file = TempFile::new().map_err(MultipartError::Spill)?;
+        file.write_all(&bytes).map_err(MultipartError::Spill)?;
+        loop {
+            let n = self.read_part(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buf[..n]).map_err(MultipartError::Spill)?;
+        }
+        file.seek(SeekFrom::Start(0)).map_err(MultipartError::Spill)?;
+        Ok(PartData::File(file))
+    }
+}
+
+impl<R: Read> Read for Part<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_part(buf).map_err(io::Error::from)
+    }
+}
@@ -0,0 +1,207 @@
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Read};
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::multipart::{Multipart, MultipartError, MultipartLimits, PartData, boundary};
+    use crate::request::Request;
+    use crate::response::StatusCode;
+
+    /// Hands out at most `chunk` bytes per read, to exercise delimiters
+    /// split across reads.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    const FORM: &[u8] = b"preamble to ignore\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Hello; world\r\n\
+        --XyZ  \r\n\
+        Content-Disposition: form-data; name=\"upload\"; filename=\"notes;v2.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        X-Extra: 1\r\n\
+        \r\n\
+        line one\r\n--XyNot a boundary\r\nline two\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    fn multipart(data: &[u8], chunk: usize) -> Multipart<Trickle<'_>> {
+        Multipart::new(Trickle { data, chunk }, "XyZ", MultipartLimits::default())
+    }
+
+    #[test]
+    fn test_boundary_from_content_type() {
+        assert_eq!(boundary("multipart/form-data; boundary=abc123").unwrap(), "abc123");
+        assert_eq!(boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b;c\"").unwrap(), "a b;c");
+        assert!(matches!(boundary("text/plain; boundary=abc"), Err(MultipartError::NotMultipart)));
+        assert!(matches!(boundary("multipart/form-data"), Err(MultipartError::InvalidBoundary(_))));
+        let long = format!("multipart/form-data; boundary={}", "a".repeat(71));
+        assert!(matches!(boundary(&long), Err(MultipartError::InvalidBoundary(_))));
+    }
+
+    #[test]
+    fn test_parts_with_names_filenames_and_headers() {
+        for chunk in [1, 3, 7, 4096] {
+            let mut form = multipart(FORM, chunk);
+
+            let mut part = form.next_part().unwrap().unwrap();
+            assert_eq!(part.name(), "title");
+            assert_eq!(part.filename(), None);
+            assert_eq!(part.text().unwrap(), "Hello; world");
+
+            let mut part = form.next_part().unwrap().unwrap();
+            assert_eq!(part.name(), "upload");
+            assert_eq!(part.filename(), Some("notes;v2.txt"));
+            assert_eq!(part.content_type(), Some("text/plain"));
+            assert_eq!(part.headers().get_value("x-extra"), Some("1"));
+            let mut body = String::new();
+            part.read_to_string(&mut body).unwrap();
+            assert_eq!(body, "line one\r\n--XyNot a boundary\r\nline two");
+
+            assert!(form.next_part().unwrap().is_none());
+            assert!(form.next_part().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_unread_parts_are_skipped() {
+        let mut form = multipart(FORM, 5);
+        form.next_part().unwrap().unwrap();
+        let part = form.next_part().unwrap().unwrap();
+        assert_eq!(part.name(), "upload");
+        assert!(form.next_part().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_extended_filename() {
+        let data = b"--XyZ\r\n\
+            Content-Disposition: form-data; name=\"f\"; filename=\"fallback.txt\"; filename*=UTF-8''na%C3%AFve.txt\r\n\
+            \r\n\
+            x\r\n--XyZ--\r\n";
+        let mut form = multipart(data, 64);
+        assert_eq!(form.next_part().unwrap().unwrap().filename(), Some("naïve.txt"));
+
+        let data = b"--XyZ\r\n\
+            Content-Disposition: form-data; name=\"f\"; filename=\"fallback.txt\"; filename*=UTF-8'en'na%C3%AFve.txt\r\n\
+            \r\n\
+            x\r\n--XyZ--\r\n";
+        let mut form = multipart(data, 64);
+        assert_eq!(form.next_part().unwrap().unwrap().filename(), Some("naïve.txt"), "Language tag is skipped");
+    }
+
+    #[test]
+    fn test_malformed_bodies() {
+        let cases: &[&[u8]] = &[
+            b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nno closing boundary",
+            b"--XyZ\r\nContent-Type: text/plain\r\n\r\nx\r\n--XyZ--",
+            b"--XyZ\r\nContent-Disposition: attachment; name=\"a\"\r\n\r\nx\r\n--XyZ--",
+            b"--XyZjunk\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nx\r\n--XyZ--",
+        ];
+        for data in cases {
+            let mut form = multipart(data, 16);
+            let err = loop {
+                match form.next_part() {
+                    Ok(Some(mut part)) => {
+                        if let Err(e) = part.to_bytes() {
+                            break e;
+                        }
+                    }
+                    Ok(None) => panic!("Expected an error for {:?}", String::from_utf8_lossy(data)),
+                    Err(e) => break e,
+                }
+            };
+            assert_eq!(err.status(), StatusCode::BadRequest, "{}", err);
+        }
+    }
+
+    fn limited(limits: MultipartLimits) -> MultipartError {
+        let mut form = Multipart::new(Trickle { data: FORM, chunk: 4 }, "XyZ", limits);
+        loop {
+            match form.next_part() {
+                Ok(Some(mut part)) => {
+                    if let Err(e) = part.to_bytes() {
+                        return e;
+                    }
+                }
+                Ok(None) => panic!("Expected a limit to be hit"),
+                Err(e) => return e,
+            }
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let err = limited(MultipartLimits { max_part_size: 10, ..Default::default() });
+        assert!(matches!(&err, MultipartError::PartTooLarge(name) if name == "title"));
+        assert_eq!(err.status(), StatusCode::ContentTooLarge);
+
+        let err = limited(MultipartLimits { max_total_size: 100, ..Default::default() });
+        assert!(matches!(err, MultipartError::BodyTooLarge));
+
+        let err = limited(MultipartLimits { max_parts: 1, ..Default::default() });
+        assert!(matches!(err, MultipartError::TooManyParts));
+
+        let err = limited(MultipartLimits { max_part_headers: 32, ..Default::default() });
+        assert!(matches!(err, MultipartError::HeadersTooLarge));
+    }
+
+    #[test]
+    fn test_spool_to_temp_file() {
+        let mut form = multipart(FORM, 16);
+        match form.next_part().unwrap().unwrap().spool(64).unwrap() {
+            PartData::Memory(bytes) => assert_eq!(bytes, b"Hello; world"),
+            PartData::File(_) => panic!("Small part should stay in memory"),
+        }
+
+        let path = match form.next_part().unwrap().unwrap().spool(8).unwrap() {
+            PartData::File(mut file) => {
+                let mut body = String::new();
+                file.read_to_string(&mut body).unwrap();
+                assert_eq!(body, "line one\r\n--XyNot a boundary\r\nline two");
+                assert!(file.path().exists());
+                let mode = file.path().metadata().unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600, "Spilled uploads must be private to the owner");
+                file.path().to_path_buf()
+            }
+            PartData::Memory(_) => panic!("Large part should be spilled"),
+        };
+        assert!(!path.exists(), "Temp file should be removed on drop");
+    }
+
+    #[test]
+    fn test_request_multipart() {
+        let mut raw = format!(
+            "POST /upload HTTP/1.1\r\nHost: localhost\r\n\
+             Content-Type: multipart/form-data; boundary=XyZ\r\nContent-Length: {}\r\n\r\n",
+            FORM.len()
+        )
+        .into_bytes();
+        raw.extend_from_slice(FORM);
+        let mut req = Request::req_from_reader(&mut BufReader::new(&raw[..])).unwrap();
+
+        let mut form = req.multipart().unwrap();
+        let mut names = Vec::new();
+        while let Some(part) = form.next_part().unwrap() {
+            names.push(part.name().to_string());
+        }
+        assert_eq!(names, vec!["title", "upload"]);
+
+        let mut req = Request::req_from_reader(&mut BufReader::new(
+            &b"POST / HTTP/1.1\r\nHost: a\r\nContent-Type: text/plain\r\nContent-Length: 1\r\n\r\nx"[..],
+        ))
+        .unwrap();
+        assert_eq!(req.multipart().err().unwrap().status(), StatusCode::UnsupportedMediaType);
+    }
+}
@@ -0,0 +1,485 @@
+use std::fmt;
+use std::io::{self, BufRead, Cursor, ErrorKind, Read, Write};
+use std::str;
+use std::sync::{Arc, Mutex};
+
+use crate::headers::{self, is_token_char, trim_ows, Headers};
+
+use super::{Limits, ParseError, Result, Version};
+
+/// Transfer codings from the IANA registry. The body is handed over still
+/// encoded with anything but chunked.
+const KNOWN_CODINGS: &[&str] = &["chunked", "compress", "deflate", "gzip", "x-compress", "x-gzip"];
+
+/// Fields that must not be sent as trailers because they control framing,
+/// routing, authentication or how the request is processed
+/// (RFC 9110 section 6.5.1).
+const FORBIDDEN_TRAILERS: &[&str] = &[
+    "authorization",
+    "cache-control",
+    "connection",
+    "content-encoding",
+    "content-length",
+    "content-range",
+    "content-type",
+    "cookie",
+    "expect",
+    "host",
+    "if-match",
+    "if-modified-since",
+    "if-none-match",
+    "if-range",
+    "if-unmodified-since",
+    "keep-alive",
+    "max-forwards",
+    "pragma",
+    "proxy-authorization",
+    "range",
+    "set-cookie",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// How the end of a body is found on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Framing {
+    Length(u64),
+    Chunked,
+}
+
+impl Framing {
+    /// Applies the message body length rules of RFC 9112 section 6.3, refusing
+    /// anything two parsers could read differently.
+    pub(crate) fn for_request(headers: &Headers, version: Version) -> Result<Option<Framing>> {
+        let has_length = headers.contains("content-length");
+        if headers.contains("transfer-encoding") {
+            if has_length {
+                return Err(ParseError::ConflictingFraming);
+            }
+            if version == Version::Http10 {
+                return Err(ParseError::InvalidTransferEncoding("not allowed in HTTP/1.0"));
+            }
+            return Self::transfer_encoding(headers).map(Some);
+        }
+        if has_length {
+            return Self::content_length(headers).map(|len| Some(Framing::Length(len)));
+        }
+        Ok(None)
+    }
+
+    /// Every Content-Length value, across lines and comma-separated lists,
+    /// must be the same run of digits.
+    fn content_length(headers: &Headers) -> Result<u64> {
+        let mut length = None;
+        for value in headers.get_all("content-length").flat_map(|v| v.split(',')) {
+            let value = trim_ows(value);
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParseError::InvalidContentLength);
+            }
+            let len: u64 = value.parse().map_err(|_| ParseError::InvalidContentLength)?;
+            if length.is_some_and(|prev| prev != len) {
+                return Err(ParseError::InvalidContentLength);
+            }
+            length = Some(len);
+        }
+        length.ok_or(ParseError::InvalidContentLength)
+    }
+
+    /// The codings, case-insensitive and across lines, must end with a single
+    /// chunked; any coding before it must be one we know of.
+    fn transfer_encoding(headers: &Headers) -> Result<Framing> {
+        if !headers.get_all("transfer-encoding").all(str::is_ascii) {
+            return Err(ParseError::InvalidTransferEncoding("non-ASCII characters in codings"));
+        }
+        let codings: Vec<&str> = headers
+            .get_all("transfer-encoding")
+            .flat_map(|v| v.split(','))
+            .map(trim_ows)
+            .filter(|c| !c.is_empty())
+            .collect();
+        let names: Vec<&str> = codings.iter().map(|c| trim_ows(c.split(';').next().unwrap_or(""))).collect();
+
+        match names.last() {
+            Some(last) if last.eq_ignore_ascii_case("chunked") => {}
+            _ => return Err(ParseError::InvalidTransferEncoding("chunked must be the final coding")),
+        }
+        if names.iter().filter(|n| n.eq_ignore_ascii_case("chunked")).count() > 1 {
+            return Err(ParseError::InvalidTransferEncoding("chunked applied more than once"));
+        }
+        if codings.last().is_some_and(|c| c.contains(';')) {
+            return Err(ParseError::InvalidTransferEncoding("chunked takes no parameters"));
+        }
+        if !names.iter().all(|n| KNOWN_CODINGS.iter().any(|k| k.eq_ignore_ascii_case(n))) {
+            return Err(ParseError::UnsupportedTransferEncoding);
+        }
+        Ok(Framing::Chunked)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    Length(u64),
+    ChunkSize,
+    ChunkData(u64),
+    Done,
+    /// An earlier read failed; the framing can no longer be trusted.
+    Failed,
+}
+
+/// Decodes one body from the connection a read at a time, enforcing the
+/// body and chunk size limits as it goes.
+#[derive(Debug)]
+pub(crate) struct Decoder {
+    state: State,
+    read: u64,
+    max_body_size: u64,
+    max_chunk_size: u64,
+    limits: Limits,
+    trailers: Option<Headers>,
+}
+
+impl Decoder {
+    pub(crate) fn new(framing: Framing, limits: &Limits) -> Self {
+        Decoder {
+            state: match framing {
+                Framing::Length(len) => State::Length(len),
+                Framing::Chunked => State::ChunkSize,
+            },
+            read: 0,
+            max_body_size: limits.max_body_size as u64,
+            max_chunk_size: limits.max_chunk_size as u64,
+            limits: limits.clone(),
+            trailers: None,
+        }
+    }
+
+    pub(crate) fn read(&mut self, reader: &mut dyn BufRead, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let result = self.read_framed(reader, buf);
+        if result.is_err() {
+            self.state = State::Failed;
+        }
+        result
+    }
+
+    fn read_framed(&mut self, reader: &mut dyn BufRead, buf: &mut [u8]) -> Result<usize> {
+        loop {
+            match self.state {
+                State::Failed => {
+                    return Err(ParseError::Io(io::Error::other("Request body failed to read earlier")));
+                }
+                State::Done | State::Length(0) => {
+                    self.state = State::Done;
+                    return Ok(0);
+                }
+                State::Length(remaining) => {
+                    let n = Self::read_some(reader, buf, remaining)?;
+                    self.state = State::Length(remaining - n as u64);
+                    return Ok(n);
+                }
+                State::ChunkSize => {
+                    let size = self.read_chunk_size(reader)?;
+                    if size == 0 {
+                        self.trailers = Some(Self::read_trailers(reader, &self.limits)?);
+                        self.state = State::Done;
+                        return Ok(0);
+                    }
+                    if size > self.max_chunk_size || self.read + size > self.max_body_size {
+                        return Err(ParseError::BodyTooLarge);
+                    }
+                    self.read += size;
+                    self.state = State::ChunkData(size);
+                }
+                State::ChunkData(0) => {
+                    let mut crlf = [0u8; 2];
+                    reader.read_exact(&mut crlf)?;
+                    if &crlf != b"\r\n" {
+                        return Err(ParseError::BadChunk("Expected CRLF after chunk data"));
+                    }
+                    self.state = State::ChunkSize;
+                }
+                State::ChunkData(remaining) => {
+                    let n = Self::read_some(reader, buf, remaining)?;
+                    self.state = State::ChunkData(remaining - n as u64);
+                    return Ok(n);
+                }
+            }
+        }
+    }
+
+    /// Reads and discards whatever is left of the body.
+    pub(crate) fn drain(&mut self, reader: &mut dyn BufRead) -> Result<()> {
+        let mut scratch = [0u8; 8192];
+        while self.read(reader, &mut scratch)? > 0 {}
+        Ok(())
+    }
+
+    fn read_some(reader: &mut dyn BufRead, buf: &mut [u8], remaining: u64) -> Result<usize> {
+        let max = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let n = reader.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(ParseError::Io(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Unexpected end of stream while reading body",
+            )));
+        }
+        Ok(n)
+    }
+
+    /// Trailer fields of a chunked body, once it has been read to the end.
+    pub(crate) fn take_trailers(&mut self) -> Option<Headers> {
+        self.trailers.take()
+    }
+
+    /// Parses `chunk-size [ chunk-ext ] CRLF`. Extensions are checked for
+    /// syntax and otherwise ignored, as RFC 9112 section 7.1.1 requires of
+    /// recipients that do not understand them.
+    fn read_chunk_size(&self, reader: &mut dyn BufRead) -> Result<u64> {
+        let line = headers::read_line(reader, self.limits.max_header_line, ParseError::BadChunk("Chunk size line too long"))?;
+        let line = str::from_utf8(&line[..line.len() - 2])
+            .map_err(|_| ParseError::BadChunk("Invalid chunk size"))?;
+
+        let size_str = match line.split_once(';') {
+            Some((size, extensions)) => {
+                Self::check_extensions(extensions)?;
+                size.trim_end_matches([' ', '\t'])
+            }
+            None => line,
+        };
+
+        // from_str_radix alone would also take a sign or surrounding spaces.
+        if size_str.is_empty() || !size_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseError::BadChunk("Invalid chunk size"));
+        }
+        u64::from_str_radix(size_str, 16).map_err(|_| ParseError::BadChunk("Chunk size too large"))
+    }
+
+    /// Checks `*( BWS ";" BWS name [ BWS "=" BWS ( token / quoted-string ) ] )`,
+    /// starting just after the first `;`.
+    fn check_extensions(extensions: &str) -> Result<()> {
+        let invalid = ParseError::BadChunk("Invalid chunk extension");
+        let bytes = extensions.as_bytes();
+        let is_token = |b: u8| is_token_char(b as char);
+        let skip_ws = |mut i: usize| {
+            while i < bytes.len() && matches!(bytes[i], b' ' | b'\t') {
+                i += 1;
+            }
+            i
+        };
+
+        let mut i = 0;
+        loop {
+            i = skip_ws(i);
+            let start = i;
+            while i < bytes.len() && is_token(bytes[i]) {
+                i += 1;
+            }
+            if i == start {
+                return Err(invalid);
+            }
+
+            i = skip_ws(i);
+            if i < bytes.len() && bytes[i] == b'=' {
+                i = skip_ws(i + 1);
+                if i < bytes.len() && bytes[i] == b'"' {
+                    i += 1;
+                    loop {
+                        match bytes.get(i) {
+                            None => return Err(invalid),
+                            Some(b'"') => break,
+                            Some(b'\\') if i + 1 < bytes.len() => i += 2,
+                            Some(_) => i += 1,
+                        }
+                    }
+                    i += 1;
+                } else {
+                    let start = i;
+                    while i < bytes.len() && is_token(bytes[i]) {
+                        i += 1;
+                    }
+                    if i == start {
+                        return Err(invalid);
+                    }
+                }
+                i = skip_ws(i);
+            }
+
+            match bytes.get(i) {
+                None => return Ok(()),
+                Some(b';') => i += 1,
+                Some(_) => return Err(invalid),
+            }
+        }
+    }
+
+    /// Reads the trailer section up to and including the final CRLF,
+    /// refusing fields that may not appear there.
+    fn read_trailers(reader: &mut dyn BufRead, limits: &Limits) -> Result<Headers> {
+        let trailers = Headers::parse_fields(reader, limits)?;
+        for (name, _) in trailers.iter() {
+            if FORBIDDEN_TRAILERS.iter().any(|f| f.eq_ignore_ascii_case(name)) {
+                return Err(ParseError::ForbiddenTrailer(name.to_ascii_lowercase()));
+            }
+        }
+        Ok(trailers)
+    }
+}
+
+/// Read side of a connection shared between the server and the `Body` of
+/// the request being served. The generation changes whenever the server
+/// takes the connection back, so a `Body` kept past its request reads
+/// nothing instead of the next request's bytes.
+pub(crate) struct Source<R: ?Sized> {
+    generation: u64,
+    decoder: Option<Decoder>,
+    /// Where to send `100 Continue` on the first body read, for a client
+    /// that is waiting for it.
+    interim: Option<Box<dyn Write + Send>>,
+    /// Set once the final response has started, after which `100 Continue`
+    /// would land inside it.
+    responding: bool,
+    pub(crate) reader: R,
+}
+
+pub(crate) type SharedSource = Arc<Mutex<Source<dyn BufRead + Send>>>;
+
+impl<R: BufRead> Source<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Source { generation: 0, decoder: None, interim: None, responding: false, reader }
+    }
+
+    /// Holds back the current body until it is first read, at which point
+    /// `100 Continue` is written to `writer`.
+    pub(crate) fn expect_continue(&mut self, writer: Box<dyn Write + Send>) {
+        self.interim = Some(writer);
+    }
+
+    /// Marks the final response as started. A body still waiting on
+    /// `100 Continue` can no longer be asked for and fails to read.
+    pub(crate) fn start_response(&mut self) {
+        self.responding = true;
+    }
+
+    /// Whether the client is still waiting for `100 Continue` before it
+    /// sends the body.
+    pub(crate) fn continue_pending(&self) -> bool {
+        self.interim.is_some() && self.decoder.is_some()
+    }
+
+    /// Discards the unread rest of the current body so the next request can
+    /// be parsed. Fails if the body was malformed, the client went away, or
+    /// the client was never told to send it, since then there is no telling
+    /// whether it will.
+    pub(crate) fn finish_body(&mut self) -> Result<()> {
+        self.generation += 1;
+        self.responding = false;
+        let pending = self.interim.take().is_some();
+        match self.decoder.take() {
+            Some(_) if pending => Err(ParseError::Io(io::Error::other("Body was never asked for"))),
+            Some(mut decoder) => decoder.drain(&mut self.reader),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A request body, read as a stream bounded by its Content-Length or chunked
+/// framing. `bytes` collects the rest of it for handlers that want it all.
+pub struct Body {
+    inner: Inner,
+    trailers: Option<Headers>,
+}
+
+enum Inner {
+    Buffered(Cursor<Vec<u8>>),
+    Streaming { source: SharedSource, generation: u64 },
+}
+
+impl Body {
+    pub fn empty() -> Self {
+        Body::from(Vec::new())
+    }
+
+    /// Starts streaming a body with the given framing from `source`.
+    pub(crate) fn streaming(source: &SharedSource, framing: Framing, limits: &Limits) -> Self {
+        let mut shared = source.lock().unwrap_or_else(|e| e.into_inner());
+        shared.decoder = Some(Decoder::new(framing, limits));
+        Body {
+            inner: Inner::Streaming { source: source.clone(), generation: shared.generation },
+            trailers: None,
+        }
+    }
+
+    pub(crate) fn with_trailers(mut self, trailers: Option<Headers>) -> Self {
+        self.trailers = trailers;
+        self
+    }
+
+    /// Trailer fields sent after a chunked body. Only known once the body
+    /// has been read to the end; `None` before that or without chunking.
+    pub fn trailers(&self) -> Option<&Headers> {
+        self.trailers.as_ref()
+    }
+
+    /// Reads the rest of the body into memory.
+    pub fn bytes(&mut self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes).map_err(ParseError::from_io)?;
+        Ok(bytes)
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::empty()
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Body { inner: Inner::Buffered(Cursor::new(bytes)), trailers: None }
+    }
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Buffered(cursor) => cursor.read(buf),
+            Inner::Streaming { source, generation } => {
+                let mut shared = source.lock().unwrap_or_else(|e| e.into_inner());
+                if shared.generation != *generation {
+                    return Ok(0);
+                }
+                let shared = &mut *shared;
+                let Some(decoder) = shared.decoder.as_mut() else {
+                    return Ok(0);
+                };
+                if shared.interim.is_some() && shared.responding {
+                    return Err(io::Error::other("Body was not asked for before the response started"));
+                }
+                if let Some(mut interim) = shared.interim.take() {
+                    interim.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                    interim.flush()?;
+                }
+                let n = decoder.read(&mut shared.reader, buf).map_err(io::Error::from)?;
+                if n == 0 && self.trailers.is_none() {
+                    self.trailers = decoder.take_trailers();
+                }
+                Ok(n)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.inner {
+            Inner::Buffered(cursor) => f.debug_struct("Body").field("buffered", &cursor.get_ref().len()).finish(),
+            Inner::Streaming { .. } => f.debug_struct("Body").field("streaming", &true).finish(),
+        }
+    }
+}
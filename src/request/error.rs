@@ -0,0 +1,137 @@
+use std::fmt;
+use std::io;
+
+use crate::response::{HandlerError, StatusCode};
+
+/// Why a request could not be parsed. Each variant maps to the status code
+/// the server answers with through `ParseError::status`.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The request line is not `method SP request-target SP HTTP-version`.
+    InvalidRequestLine(&'static str),
+    /// The method is not a valid token.
+    InvalidMethod,
+    /// A well-formed method the server does not implement.
+    MethodNotImplemented(String),
+    InvalidTarget,
+    /// A request target that is malformed or would escape the root, such as
+    /// one with a fragment or a `/../` above `/`.
+    InvalidUri(&'static str),
+    UriTooLong,
+    /// The HTTP-version is not of the form `HTTP/x.y`.
+    InvalidVersion,
+    /// A well-formed HTTP-version other than HTTP/1.x.
+    UnsupportedVersion,
+    InvalidHeader(&'static str),
+    DuplicateHeader(String),
+    MissingHost,
+    /// A header line, the number of headers or their total size is over the limit.
+    HeaderTooLarge,
+    BodyTooLarge,
+    InvalidContentLength,
+    /// Both Content-Length and Transfer-Encoding were sent.
+    ConflictingFraming,
+    /// A transfer coding other than chunked that the server cannot decode.
+    UnsupportedTransferEncoding,
+    /// Transfer-Encoding that leaves the body length ambiguous, such as a
+    /// list not ending in chunked (RFC 9112 section 6.3).
+    InvalidTransferEncoding(&'static str),
+    BadChunk(&'static str),
+    /// A trailer field that may only be sent in the header section.
+    ForbiddenTrailer(String),
+    /// An `Expect` member other than `100-continue`.
+    UnsupportedExpectation(String),
+    Io(io::Error),
+}
+
+impl ParseError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ParseError::MethodNotImplemented(_) => StatusCode::NotImplemented,
+            ParseError::UnsupportedTransferEncoding => StatusCode::NotImplemented,
+            ParseError::UnsupportedVersion => StatusCode::HttpVersionNotSupported,
+            ParseError::UriTooLong => StatusCode::UriTooLong,
+            ParseError::HeaderTooLarge => StatusCode::RequestHeaderFieldsTooLarge,
+            ParseError::BodyTooLarge => StatusCode::ContentTooLarge,
+            ParseError::UnsupportedExpectation(_) => StatusCode::ExpectationFailed,
+            ParseError::Io(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {
+                StatusCode::RequestTimeout
+            }
+            _ => StatusCode::BadRequest,
+        }
+    }
+
+    /// Recovers a `ParseError` that was passed through an `io::Error`, as
+    /// happens when reading a `Body`.
+    pub(crate) fn from_io(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<ParseError>()) {
+            return *e.into_inner().unwrap().downcast::<ParseError>().unwrap();
+        }
+        ParseError::Io(e)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidRequestLine(reason) => write!(f, "Invalid request line: {}", reason),
+            ParseError::InvalidMethod => write!(f, "Invalid HTTP method"),
+            ParseError::MethodNotImplemented(method) => write!(f, "Method not implemented: {}", method),
+            ParseError::InvalidTarget => write!(f, "Invalid request target format"),
+            ParseError::InvalidUri(reason) => write!(f, "Invalid request target: {}", reason),
+            ParseError::UriTooLong => write!(f, "Request target is too long"),
+            ParseError::InvalidVersion => write!(f, "HTTP version must be of the form 'HTTP/x.y'"),
+            ParseError::UnsupportedVersion => write!(f, "HTTP version not supported"),
+            ParseError::InvalidHeader(reason) => write!(f, "Invalid header: {}", reason),
+            ParseError::DuplicateHeader(name) => write!(f, "Duplicate singleton header: {}", name),
+            ParseError::MissingHost => write!(f, "Host header is required for origin target"),
+            ParseError::HeaderTooLarge => write!(f, "Request header fields too large"),
+            ParseError::BodyTooLarge => write!(f, "Request body too large"),
+            ParseError::InvalidContentLength => write!(f, "Invalid Content-Length value"),
+            ParseError::ConflictingFraming => {
+                write!(f, "Cannot have both Content-Length and Transfer-Encoding headers")
+            }
+            ParseError::UnsupportedTransferEncoding => write!(f, "Unsupported transfer coding"),
+            ParseError::InvalidTransferEncoding(reason) => write!(f, "Invalid Transfer-Encoding: {}", reason),
+            ParseError::BadChunk(reason) => write!(f, "Invalid chunked body: {}", reason),
+            ParseError::ForbiddenTrailer(name) => write!(f, "Field not allowed in trailers: {}", name),
+            ParseError::UnsupportedExpectation(value) => write!(f, "Unsupported expectation: {}", value),
+            ParseError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> Self {
+        match e {
+            ParseError::Io(e) => e,
+            other => io::Error::new(io::ErrorKind::InvalidData, other),
+        }
+    }
+}
+
+/// Lets handlers use `?` on body reads; the response gets the status the
+/// parse error maps to.
+impl From<ParseError> for HandlerError {
+    fn from(e: ParseError) -> Self {
+        HandlerError {
+            status: e.status(),
+            message: format!("{}\n", e),
+        }
+    }
+}
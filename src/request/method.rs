@@ -0,0 +1,84 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Request method. Methods are case-sensitive tokens (RFC 9110 section 9.1);
+/// anything that is a valid token but not a standard method is kept as
+/// `Extension`, e.g. WebDAV's `PROPFIND`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    Extension(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+            Method::Extension(token) => token,
+        }
+    }
+
+    pub fn is_extension(&self) -> bool {
+        matches!(self, Method::Extension(_))
+    }
+}
+
+/// Returned when a method is not a valid token.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidMethod;
+
+impl fmt::Display for InvalidMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Invalid HTTP method")
+    }
+}
+
+impl std::error::Error for InvalidMethod {}
+
+impl FromStr for Method {
+    type Err = InvalidMethod;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "CONNECT" => Method::Connect,
+            "OPTIONS" => Method::Options,
+            "TRACE" => Method::Trace,
+            "PATCH" => Method::Patch,
+            _ if !s.is_empty() && s.chars().all(crate::headers::is_token_char) => Method::Extension(s.to_string()),
+            _ => return Err(InvalidMethod),
+        })
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<&str> for Method {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
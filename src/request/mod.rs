@@ -1,47 +1,113 @@
+mod body;
+mod error;
+mod method;
+mod smuggling_test;
 pub mod test;
+mod version;
 
-use std::collections::HashMap;
-use std::io::{BufRead, Error, ErrorKind, Result, Read};
+use std::io::BufRead;
 use std::str;
 
-const SINGLETON_HEADERS: &[&str] = &[
-    "content-length",
-    "host",
-    "authorization",
-    "content-type",
-    "content-encoding",
-    "content-range",
-    "date",
-];
+use crate::cookie::{self, Keys};
+use crate::extensions::Extensions;
+use crate::form::{Form, FromForm};
+use crate::headers::{self, Headers};
+use crate::multipart::{self, Multipart, MultipartError, MultipartLimits};
+use crate::response::{HandlerError, StatusCode};
+
+type Result<T> = std::result::Result<T, ParseError>;
+use crate::router::Params;
+use crate::session::Session;
+use crate::uri::Uri;
+use body::{Decoder, Framing};
+
+pub use body::Body;
+pub(crate) use body::{SharedSource, Source};
+pub use error::ParseError;
+pub use method::{InvalidMethod, Method};
+pub use version::{Version, VersionError};
 
 #[derive(Debug)]
 pub struct RequestLine {
-    http_version: String,
-    method: String,
+    http_version: Version,
+    method: Method,
     request_target: String,
 }
 
+/// Size caps enforced while parsing so a single client cannot exhaust
+/// memory. Line lengths include the trailing CRLF.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// Longest request line; longer ones are answered with 414.
+    pub max_request_line: usize,
+    /// Longest single header line; longer ones are answered with 431.
+    pub max_header_line: usize,
+    /// Most header lines per request; more are answered with 431.
+    pub max_headers: usize,
+    /// Largest combined size of all header lines; larger is answered with 431.
+    pub max_header_bytes: usize,
+    /// Largest body, whether framed by Content-Length or chunked; larger is
+    /// answered with 413.
+    pub max_body_size: usize,
+    /// Largest single chunk of a chunked body; larger is answered with 413.
+    pub max_chunk_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_request_line: 8 * 1024,
+            max_header_line: 8 * 1024,
+            max_headers: 100,
+            max_header_bytes: 64 * 1024,
+            max_body_size: 10 * 1024 * 1024,
+            max_chunk_size: 1024 * 1024,
+        }
+    }
+}
+
+/// Parser settings beyond what HTTP itself requires.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Extension methods (e.g. `PROPFIND`) to accept. Any other non-standard
+    /// method is answered with `501 Not Implemented`.
+    pub extension_methods: Vec<String>,
+    pub limits: Limits,
+}
+
 #[derive(Debug)]
 pub struct Request {
     pub request_line: RequestLine,
-    headers: HashMap<String, String>,
-    body: Vec<u8>,
+    uri: Uri,
+    headers: Headers,
+    body: Body,
+    extensions: Extensions,
+    params: Params,
+}
+
+impl Default for Request {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Request {
     pub fn new() -> Self {
         Request {
             request_line: RequestLine {
-                http_version: String::new(),
-                method: String::new(),
+                http_version: Version::Http11,
+                method: Method::Get,
                 request_target: String::new(),
             },
-            headers: HashMap::new(),
-            body: Vec::new(),
+            uri: Uri::default(),
+            headers: Headers::new(),
+            body: Body::empty(),
+            extensions: Extensions::new(),
+            params: Params::default(),
         }
     }
 
-    pub fn method(&self) -> &str {
+    pub fn method(&self) -> &Method {
         &self.request_line.method
     }
 
@@ -49,288 +115,337 @@ impl Request {
         &self.request_line.request_target
     }
 
+    /// The request target, parsed and normalized.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// The normalized path, without the query string.
     pub fn path(&self) -> &str {
-        match self.request_line.request_target.find("://") {
-            Some(idx) => {
-                let after_scheme = &self.request_line.request_target[idx + 3..];
-                match after_scheme.find('/') {
-                    Some(path_start) => &after_scheme[path_start..],
-                    None => "/",
-                }
-            }
-            None => {
-                if self.request_line.request_target.starts_with('/') {
-                    &self.request_line.request_target
-                } else {
-                    "/"
-                }
-            }
+        self.uri.path()
+    }
+
+    /// The query string without the leading `?`, still percent-encoded.
+    pub fn query(&self) -> Option<&str> {
+        self.uri.query()
+    }
+
+    /// The query string parsed into name and value pairs. Malformed
+    /// escapes are answered with 400.
+    pub fn query_params(&self) -> std::result::Result<Form, HandlerError> {
+        Form::parse(self.query().unwrap_or(""))
+    }
+
+    /// Builds `T` from the query string; see `FromForm`.
+    pub fn query_as<T: FromForm>(&self) -> std::result::Result<T, HandlerError> {
+        T::from_form(&self.query_params()?)
+    }
+
+    /// Reads an `application/x-www-form-urlencoded` body. Other content
+    /// types are answered with 415 and malformed bodies with 400.
+    pub fn form(&mut self) -> std::result::Result<Form, HandlerError> {
+        let media_type = self.header("Content-Type").and_then(|v| v.split(';').next()).map(str::trim);
+        if !media_type.is_some_and(|m| m.eq_ignore_ascii_case("application/x-www-form-urlencoded")) {
+            return Err(HandlerError {
+                status: StatusCode::UnsupportedMediaType,
+                message: "Expected an application/x-www-form-urlencoded body\n".to_string(),
+            });
         }
+
+        let bytes = Body::bytes(&mut self.body)?;
+        let body = str::from_utf8(&bytes).map_err(|_| HandlerError {
+            status: StatusCode::BadRequest,
+            message: "Form body is not valid UTF-8\n".to_string(),
+        })?;
+        Form::parse(body)
+    }
+
+    /// Builds `T` from a form body; see `form` and `FromForm`.
+    pub fn form_as<T: FromForm>(&mut self) -> std::result::Result<T, HandlerError> {
+        T::from_form(&self.form()?)
+    }
+
+    /// Reads a `multipart/form-data` body part by part, with the default
+    /// `MultipartLimits`. Other content types are answered with 415.
+    pub fn multipart(&mut self) -> std::result::Result<Multipart<&mut Body>, MultipartError> {
+        self.multipart_with(MultipartLimits::default())
     }
 
+    pub fn multipart_with(&mut self, limits: MultipartLimits) -> std::result::Result<Multipart<&mut Body>, MultipartError> {
+        let boundary = multipart::boundary(self.header("Content-Type").unwrap_or(""))?;
+        Ok(Multipart::new(&mut self.body, &boundary, limits))
+    }
+
+    /// The non-empty path segments, percent-decoded.
     pub fn path_segments(&self) -> Vec<&str> {
-        self.path().split('/').filter(|s| !s.is_empty()).collect()
+        self.uri.segments().collect()
     }
 
-    pub fn http_version(&self) -> &str {
-        &self.request_line.http_version
+    pub fn http_version(&self) -> Version {
+        self.request_line.http_version
     }
 
     pub fn header(&self, key: &str) -> Option<&str> {
-        self.headers.get(&key.to_ascii_lowercase()).map(|s| s.as_str())
+        self.headers.get_value(key)
     }
 
-    pub fn headers(&self) -> Option<HashMap<String, String>> {
-        Some(self.headers.clone())
+    pub fn headers(&self) -> &Headers {
+        &self.headers
     }
 
-    pub fn body(&self) -> Option<&[u8]> {
-        if self.body.is_empty() { None }
-        else { Some(&self.body) }
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
     }
 
-    fn read_as_bytes(reader: &mut dyn BufRead) -> Result<Vec<u8>> {
-        let mut buf = Vec::new();
-        let mut temp = Vec::new();
-        loop {
-            let n = reader.read_until(b'\n', &mut temp)?;
-            if n == 0 {
-                return Err(Error::new(
-                    ErrorKind::UnexpectedEof,
-                    "Unexpected end of stream while reading line",
-                ));
-            }
+    /// Name and value pairs from the `Cookie` header, in order.
+    pub fn cookies(&self) -> Vec<(&str, &str)> {
+        cookie::parse(self.headers.get_all("Cookie"))
+    }
 
-            buf.extend_from_slice(&temp);
+    /// The value of the first cookie called `name`.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies().into_iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
 
-            if buf.len() >= 2 && &buf[buf.len() - 2..] == b"\r\n" {
-                break;
-            }
+    /// The value of the cookie `name` made by `Keys::sign`, if one of the
+    /// cookies sent under that name carries a valid signature.
+    pub fn signed_cookie(&self, keys: &Keys, name: &str) -> Option<String> {
+        self.cookies().into_iter().filter(|(n, _)| *n == name).find_map(|(_, v)| keys.verify(name, v))
+    }
+
+    /// The value of the cookie `name` made by `Keys::encrypt`, if one of the
+    /// cookies sent under that name decrypts.
+    pub fn private_cookie(&self, keys: &Keys, name: &str) -> Option<String> {
+        self.cookies().into_iter().filter(|(n, _)| *n == name).find_map(|(_, v)| keys.decrypt(name, v))
+    }
+
+    /// Whether the client expects the connection to stay open after this
+    /// request. HTTP/1.1 connections are persistent unless `Connection: close`
+    /// is sent, HTTP/1.0 connections only with `Connection: keep-alive`.
+    pub fn keep_alive(&self) -> bool {
+        let has_token = |token: &str| {
+            self.headers
+                .get_all("Connection")
+                .any(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+        };
+
+        if has_token("close") {
+            return false;
+        }
+
+        match self.http_version() {
+            Version::Http10 => has_token("keep-alive"),
+            Version::Http11 => true,
         }
-        Ok(buf)
     }
 
-    fn verify_target_url(method: &str, target: &str) -> std::io::Result<&'static str> {
+    /// Whether the client sent `Expect: 100-continue` and is waiting for the
+    /// go-ahead before sending the body. The server gives it on the first
+    /// read of the body. HTTP/1.0 clients cannot expect it (RFC 9110
+    /// section 10.1.1).
+    pub fn expects_continue(&self) -> bool {
+        self.http_version() != Version::Http10
+            && self.headers
+                .get_all("Expect")
+                .flat_map(|v| v.split(','))
+                .any(|e| e.trim().eq_ignore_ascii_case("100-continue"))
+    }
+
+    /// The request body. Read it as a stream, or call `bytes` to collect it.
+    pub fn body(&mut self) -> &mut Body {
+        &mut self.body
+    }
+
+    /// Trailer fields of a chunked body; see `Body::trailers`.
+    pub fn trailers(&self) -> Option<&Headers> {
+        self.body.trailers()
+    }
+
+    pub fn into_body(self) -> Body {
+        self.body
+    }
+
+    pub fn set_body(&mut self, body: Body) {
+        self.body = body;
+    }
+
+    /// Path parameters captured by the `Router` route that matched this request.
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name)
+    }
+
+    /// Parses a path parameter, answering 400 when it is missing or malformed.
+    pub fn param_as<T: std::str::FromStr>(&self, name: &str) -> std::result::Result<T, HandlerError> {
+        let raw = self.param(name).ok_or_else(|| HandlerError {
+            status: StatusCode::BadRequest,
+            message: format!("Missing path parameter '{}'\n", name),
+        })?;
+        raw.parse().map_err(|_| HandlerError {
+            status: StatusCode::BadRequest,
+            message: format!("Invalid value '{}' for path parameter '{}'\n", raw, name),
+        })
+    }
+
+    pub(crate) fn set_params(&mut self, params: Params) {
+        self.params = params;
+    }
+
+    /// Application state registered on the server with `Config::with_state`.
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+
+    /// The session set up by `SessionLayer`, if the handler runs behind one.
+    pub fn session(&self) -> Option<&Session> {
+        self.extensions.get::<Session>()
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    fn verify_target_url(method: &Method, target: &str) -> Result<&'static str> {
         if target.starts_with("http://") || target.starts_with("https://") {
             Ok("absolute")
         } else if target.starts_with('/') {
             Ok("origin")
         } else if target == "*" {
             Ok("asterisk")
-        } else if method == "CONNECT" && target.contains(":") {
+        } else if *method == Method::Connect && target.contains(":") {
             Ok("authority")
         } else {
-            Err(Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid request target format",
-            ))
+            Err(ParseError::InvalidTarget)
         }
     }
 
-    fn parse_request_line(reader: &mut dyn BufRead) -> std::io::Result<RequestLine> {
-        let line = Self::read_as_bytes(reader)?;
+    fn parse_request_line(reader: &mut dyn BufRead, options: &ParseOptions) -> Result<RequestLine> {
+        let line = headers::read_line(reader, options.limits.max_request_line, ParseError::UriTooLong)?;
         if line.len() < 2 || !line.ends_with(b"\r\n") {
-            return Err(Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Request line must end with CRLF",
-            ));
+            return Err(ParseError::InvalidRequestLine("Request line must end with CRLF"));
         }
 
         let line_str = str::from_utf8(&line[..line.len() - 2])
-            .map_err(|_| Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 in request line"))?;
+            .map_err(|_| ParseError::InvalidRequestLine("Invalid UTF-8 in request line"))?;
 
         let parts: Vec<&str> = line_str.split_whitespace().collect();
         if parts.len() != 3 {
-            return Err(Error::new(
-                std::io::ErrorKind::InvalidData,
+            return Err(ParseError::InvalidRequestLine(
                 "Request line must have exactly three parts: method, request target, and HTTP version",
             ));
         }
 
-        let request_line = RequestLine {
-            method: parts[0].to_string(),
-            request_target: parts[1].to_string(),
-            http_version: parts[2].to_string(),
-        };
-
-        match request_line.method.as_str() {
-            "GET" | "POST" | "PUT" | "DELETE" | "HEAD" | "OPTIONS" | "PATCH" | "CONNECT" => {},
-            _ => {
-                return Err(Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Invalid HTTP method",
-                ));
-            }
-        }
-
-        if !request_line.http_version.starts_with("HTTP/") {
-            return Err(Error::new(
-                std::io::ErrorKind::InvalidData,
-                "HTTP version must start with 'HTTP/'",
-            ));
+        let method: Method = parts[0].parse().map_err(|_| ParseError::InvalidMethod)?;
+        if let Method::Extension(token) = &method
+            && !options.extension_methods.iter().any(|m| m == token)
+        {
+            return Err(ParseError::MethodNotImplemented(token.clone()));
         }
 
-        Ok(request_line)
-    }
+        let http_version = Version::parse(parts[2]).map_err(|e| match e {
+            VersionError::Malformed => ParseError::InvalidVersion,
+            VersionError::Unsupported => ParseError::UnsupportedVersion,
+        })?;
 
-    fn is_token_char(c: char) -> bool {
-        c.is_ascii_alphanumeric() || matches!(c,
-            '!' | '#' | '$' | '%' | '&' | '\'' | '*' | '+' |
-            '-' | '.' | '^' | '_' | '`' | '|' | '~'
-        )
+        Ok(RequestLine {
+            method,
+            request_target: parts[1].to_string(),
+            http_version,
+        })
     }
 
-    pub fn parse_header_line(reader: &mut dyn BufRead) -> std::io::Result<HashMap<String, String>> {
-        let mut line = Self::read_as_bytes(reader)?;
-        if line == b"\r\n" {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Unexpected CRLF after request line (violates RFC 9112)",
-            ));
-        }
-
-        let mut headers: HashMap<String, String> = HashMap::new();
-        let mut line_str;
-
-        loop {
-            if line == b"\r\n" { break; }
-
-            line_str = str::from_utf8(&line[..line.len() - 2])
-                .map_err(|_| Error::new(std::io::ErrorKind::InvalidData, "Invalid UTF-8 in header line"))?;
-            if let Some((key, value)) = line_str.split_once(':') {
-
-                if key.chars().any(|c| c.is_ascii_whitespace()) {
-                    return Err(Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "Invalid header should not allow whitespace in field name",
-                    ));
-                } else if key.chars().any(|c| !Self::is_token_char(c)) {
-                    return Err(Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "Invalid header should not allow alphanumeric characters in field name",
-                    ));
-                }
-
-                let key_lower = key.to_ascii_lowercase();
-                let value_trimmed = value.trim();
-
-                if headers.contains_key(&key_lower) && SINGLETON_HEADERS.contains(&key_lower.as_str()) {
-                    return Err(Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "Duplicate singleton header should cause error",
-                    ));
-                } else if headers.contains_key(&key_lower) && !SINGLETON_HEADERS.contains(&key_lower.as_str()) {
-                    println!("Warning: Duplicate header found: {}", key_lower);
-                    if let Some(existing_value) = headers.get_mut(&key_lower) {
-                        existing_value.push_str(", ");
-                        existing_value.push_str(value_trimmed);
-                    }
-                } else {
-                    headers.
-                        insert(
-                            key_lower,
-                            value_trimmed.to_string(),
-                        );
-                }
-            } else {
-                return Err(Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Invalid header format",
-                ));
-            }
-
-            line = Self::read_as_bytes(reader)?;
-        }
-
-        Ok(headers)
+    pub fn req_from_reader(reader: &mut dyn BufRead) -> Result<Request> {
+        Self::req_from_reader_with(reader, &ParseOptions::default())
     }
 
-    fn read_chunked_body(reader: &mut dyn BufRead) -> std::io::Result<Vec<u8>> {
-        let mut body: Vec<u8> = Vec::new();
-
-        loop {
-            let mut size_line = String::new();
-            reader.read_line(&mut size_line)?;
-            let size_str = size_line.trim_end();
-
-            let chunk_size = usize::from_str_radix(size_str, 16).map_err(|_| {
-                Error::new(ErrorKind::InvalidData, "Invalid chunk size")
-            })?;
-
-            if chunk_size == 0 { break; }
-
-            let mut chunk = vec![0; chunk_size];
-            reader.read_exact(&mut chunk)?;
-            body.extend(chunk);
-
-            let mut crlf = [0u8; 2];
-            reader.read_exact(&mut crlf)?;
-            if &crlf != b"\r\n" {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Expected CRLF after chunk data",
-                ));
+    pub fn req_from_reader_with(reader: &mut dyn BufRead, options: &ParseOptions) -> Result<Request> {
+        let mut req = Self::read_head(reader, options)?;
+        if let Some(framing) = req.framing(options)? {
+            let mut decoder = Decoder::new(framing, &options.limits);
+            let mut body = Vec::new();
+            let mut buf = [0u8; 8192];
+            loop {
+                let n = decoder.read(reader, &mut buf)?;
+                if n == 0 { break; }
+                body.extend_from_slice(&buf[..n]);
             }
+            req.body = Body::from(body).with_trailers(decoder.take_trailers());
         }
-
-        Ok(body)
+        Ok(req)
     }
 
-    pub fn req_from_reader(reader: &mut dyn BufRead) -> Result<Request> {
-        let mut request_line = Self::parse_request_line(reader)?;
+    /// Parses the request line and headers, leaving the body unread so the
+    /// server can stream it to the handler.
+    pub(crate) fn read_head(reader: &mut dyn BufRead, options: &ParseOptions) -> Result<Request> {
+        let mut request_line = Self::parse_request_line(reader, options)?;
 
-        let headers = Self::parse_header_line(reader)?;
-        if headers.contains_key("content-length") && headers.contains_key("transfer-encoding") {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Cannot have both Content-Length and Transfer-Encoding headers",
-            ));
-        }
+        let headers = Headers::parse_with(reader, &options.limits)?;
 
         let form = Self::verify_target_url(&request_line.method, &request_line.request_target)?;
         let scheme = "http";
 
         match form {
             "origin" => {
-                let host = headers.get("host")
-                    .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Host header is required for origin target"))?;
+                let host = headers.get_value("host")
+                    .ok_or(ParseError::MissingHost)?;
+                Uri::parse_authority(host)?;
                 request_line.request_target = format!("{}://{}{}", scheme, host, request_line.request_target);
             }
             "authority" => {
                 request_line.request_target = format!("{}://{}", scheme, request_line.request_target);
             }
             "asterisk" | "absolute" => {}
-            _ => {
-                return Err(Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "Invalid request target format",
-                ));
-            }
-        }
-
-        let mut body: Vec<u8> = Vec::new();
-        if let Some(content_length) = headers.get("content-length") {
-            let len: usize = content_length.parse().map_err(|_| {
-                Error::new(ErrorKind::InvalidData, "Invalid Content-Length value")
-            })?;
-            let mut limited = reader.take(len as u64);
-            limited.read_to_end(&mut body).map_err(|e| {
-                Error::new(ErrorKind::UnexpectedEof, format!("Failed to read body: {}", e))
-            })?;
-        } else if let Some(transfer_encoding) = headers.get("transfer-encoding") {
-            if transfer_encoding != "chunked" {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    "Unsupported Transfer-Encoding, only 'chunked' is supported",
-                ));
-            }
-            body = Self::read_chunked_body(reader)?;
+            _ => return Err(ParseError::InvalidTarget),
         }
 
-        Ok(Request {
+        let uri = Uri::parse(&request_line.request_target)?;
+        let req = Request {
             request_line,
+            uri,
             headers,
-            body,
-        })
+            body: Body::empty(),
+            extensions: Extensions::new(),
+            params: Params::default(),
+        };
+        // Settle the framing now so a smuggling attempt is refused before
+        // any handler runs.
+        req.framing(options)?;
+
+        let unsupported = req.headers
+            .get_all("Expect")
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .find(|e| !e.is_empty() && !e.eq_ignore_ascii_case("100-continue"));
+        if let Some(expectation) = unsupported {
+            return Err(ParseError::UnsupportedExpectation(expectation.to_string()));
+        }
+        Ok(req)
+    }
+
+    /// Works out how the body is delimited, rejecting bodies whose declared
+    /// length is over the limit before any of it is read.
+    fn framing(&self, options: &ParseOptions) -> Result<Option<Framing>> {
+        let framing = Framing::for_request(&self.headers, self.http_version())?;
+        if let Some(Framing::Length(len)) = framing
+            && len > options.limits.max_body_size as u64
+        {
+            return Err(ParseError::BodyTooLarge);
+        }
+        Ok(framing)
+    }
+
+    /// Hooks the body up to the connection so the handler reads it directly.
+    pub(crate) fn stream_body(&mut self, source: &SharedSource, options: &ParseOptions) -> Result<()> {
+        if let Some(framing) = self.framing(options)? {
+            self.body = Body::streaming(source, framing, &options.limits);
+        }
+        Ok(())
     }
 }
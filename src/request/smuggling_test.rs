@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+    use std::net::TcpStream;
+
+    use crate::request::test::parse;
+    use crate::request::{ParseError, Request};
+    use crate::response::{HandlerError, StatusCode, Writer};
+    use crate::server::Server;
+    use crate::server::test::roundtrip;
+
+    fn post(framing: &str, body: &str) -> Vec<u8> {
+        format!("POST / HTTP/1.1\r\nHost: localhost\r\n{}\r\n{}", framing, body).into_bytes()
+    }
+
+    fn body_of(request: &[u8]) -> Vec<u8> {
+        let mut req = parse(request).expect("Expected request to be accepted");
+        req.body().bytes().expect("Expected body to be readable")
+    }
+
+    fn status_of(request: &[u8]) -> StatusCode {
+        parse(request).expect_err("Expected request to be rejected").status()
+    }
+
+    #[test]
+    fn test_cl_te_and_te_cl_are_rejected() {
+        let cl_te = post("Content-Length: 13\r\nTransfer-Encoding: chunked\r\n", "0\r\n\r\nSMUGGLED");
+        assert!(matches!(parse(&cl_te), Err(ParseError::ConflictingFraming)));
+
+        let te_cl = post("Transfer-Encoding: chunked\r\nContent-Length: 3\r\n", "8\r\nSMUGGLED\r\n0\r\n\r\n");
+        assert!(matches!(parse(&te_cl), Err(ParseError::ConflictingFraming)));
+    }
+
+    #[test]
+    fn test_malformed_content_length_is_rejected() {
+        for value in ["+5", "-5", "0x5", "5 5", "5;", "", "5, 6", "99999999999999999999999"] {
+            let request = post(&format!("Content-Length: {}\r\n", value), "hello");
+            assert!(
+                matches!(parse(&request), Err(ParseError::InvalidContentLength)),
+                "Content-Length '{}' should be rejected", value,
+            );
+        }
+    }
+
+    #[test]
+    fn test_content_length_lists_must_agree() {
+        assert_eq!(body_of(&post("Content-Length: 5, 5\r\n", "hello")), b"hello");
+        assert_eq!(body_of(&post("Content-Length: 5\r\nContent-Length: 5\r\n", "hello")), b"hello");
+
+        let differing = post("Content-Length: 5\r\nContent-Length: 6\r\n", "hello!");
+        assert_eq!(status_of(&differing), StatusCode::BadRequest);
+    }
+
+    #[test]
+    fn test_transfer_encoding_is_case_insensitive_list() {
+        let chunked = "5\r\nhello\r\n0\r\n\r\n";
+        for value in ["CHUNKED", "Chunked", "gzip, chunked", "gzip,chunked", "chunked, "] {
+            let request = post(&format!("Transfer-Encoding: {}\r\n", value), chunked);
+            assert_eq!(body_of(&request), b"hello", "Transfer-Encoding '{}' should be chunked", value);
+        }
+
+        let split = post("Transfer-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n", chunked);
+        assert_eq!(body_of(&split), b"hello");
+    }
+
+    #[test]
+    fn test_transfer_encoding_not_ending_in_chunked_is_400() {
+        for value in ["gzip", "chunked, gzip", "xchunked", "chunked, chunked", "chunked;x=1", ""] {
+            let request = post(&format!("Transfer-Encoding: {}\r\n", value), "5\r\nhello\r\n0\r\n\r\n");
+            assert!(
+                matches!(parse(&request), Err(ParseError::InvalidTransferEncoding(_))),
+                "Transfer-Encoding '{}' should be rejected", value,
+            );
+            assert_eq!(status_of(&request), StatusCode::BadRequest);
+        }
+    }
+
+    #[test]
+    fn test_unknown_coding_before_chunked_is_501() {
+        let request = post("Transfer-Encoding: rot13, chunked\r\n", "5\r\nhello\r\n0\r\n\r\n");
+        assert_eq!(status_of(&request), StatusCode::NotImplemented);
+    }
+
+    #[test]
+    fn test_http10_transfer_encoding_is_rejected() {
+        let request = b"POST / HTTP/1.0\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        assert!(matches!(parse(request), Err(ParseError::InvalidTransferEncoding(_))));
+    }
+
+    #[test]
+    fn test_obfuscated_header_names_are_rejected() {
+        for framing in ["Transfer-Encoding : chunked\r\n", " Transfer-Encoding: chunked\r\n", "Transfer_Encoding\u{0}: chunked\r\n"] {
+            let request = post(framing, "5\r\nhello\r\n0\r\n\r\n");
+            assert_eq!(status_of(&request), StatusCode::BadRequest, "{:?} should be rejected", framing);
+        }
+    }
+
+    #[test]
+    fn test_bare_lf_and_nul_in_field_values_are_rejected() {
+        for framing in ["X: a\nTransfer-Encoding: chunked\r\n", "X: a\u{0}Transfer-Encoding: chunked\r\n", "X: a\rb\r\n"] {
+            let request = post(framing, "5\r\nhello\r\n0\r\n\r\n");
+            assert_eq!(status_of(&request), StatusCode::BadRequest, "{:?} should be rejected", framing);
+        }
+    }
+
+    #[test]
+    fn test_framing_padded_with_non_ows_whitespace_is_rejected() {
+        for framing in [
+            "Transfer-Encoding: \u{b}chunked\r\n",
+            "Transfer-Encoding: chunked\u{a0}\r\n",
+            "Content-Length: \u{c}3\r\n",
+        ] {
+            let request = post(framing, "3\r\nabc\r\n0\r\n\r\n");
+            assert_eq!(status_of(&request), StatusCode::BadRequest, "{:?} should be rejected", framing);
+        }
+    }
+
+    #[test]
+    fn test_malformed_chunk_sizes_are_rejected() {
+        for size in ["+5", " 5", "5 ", "0x5", "-5", "10000000000000000", ""] {
+            let request = post("Transfer-Encoding: chunked\r\n", &format!("{}\r\nhello\r\n0\r\n\r\n", size));
+            assert!(
+                matches!(parse(&request), Err(ParseError::BadChunk(_))),
+                "Chunk size '{}' should be rejected", size,
+            );
+        }
+    }
+
+    #[test]
+    fn test_server_closes_after_smuggling_attempt() {
+        let handler = |req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            let _ = res.write_body(req.path().as_bytes());
+            Ok(())
+        };
+        let server = Server::start(0, handler).expect("Failed to start server");
+
+        let response = roundtrip(
+            &server,
+            b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: +44\r\n\r\n\
+              GET /admin HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+        assert!(!response.contains("/admin"), "Smuggled request must not be served");
+        assert_eq!(response.matches("HTTP/1.1").count(), 1);
+    }
+}
@@ -32,10 +32,18 @@ impl Read for ChunkReader {
     }
 }
 
+/// Parses `request` from a reader that hands it out a few bytes at a time.
+#[cfg(test)]
+pub(crate) fn parse(request: &[u8]) -> Result<crate::request::Request, crate::request::ParseError> {
+    let mut reader = io::BufReader::new(ChunkReader::new(request, 3));
+    crate::request::Request::req_from_reader(&mut reader)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::request::Request;
+    use crate::request::{Limits, Method, ParseError, ParseOptions, Request, Version};
+    use crate::response::StatusCode;
     use std::io::BufReader;
 
     #[test]
@@ -76,7 +84,7 @@ mod tests {
 
     #[test]
     fn test_post_request() {
-        let request = b"POST /submit HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 18\r\n\r\n{\"key\":\"value\"}";
+        let request = b"POST /submit HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 15\r\n\r\n{\"key\":\"value\"}";
         let chunk_reader = ChunkReader::new(request, 3);
         let mut reader = BufReader::new(chunk_reader);
         let req = Request::req_from_reader(&mut reader).expect("Failed to parse request");
@@ -86,7 +94,7 @@ mod tests {
         assert_eq!(req.request_line.http_version, "HTTP/1.1");
         assert_eq!(req.header("Host").unwrap(), "localhost");
         assert_eq!(req.header("Content-Type").unwrap(), "application/json");
-        assert_eq!(req.header("Content-Length").unwrap(), "18");
+        assert_eq!(req.header("Content-Length").unwrap(), "15");
     }
 
     #[test]
@@ -112,4 +120,286 @@ mod tests {
             "Expected error for invalid method in request line"
         );
     }
+
+    #[test]
+    fn test_keep_alive_defaults_per_version() {
+        let cases: &[(&[u8], bool)] = &[
+            (b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n", true),
+            (b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: Close\r\n\r\n", false),
+            (b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n", false),
+            (b"GET / HTTP/1.0\r\nHost: localhost\r\nConnection: Keep-Alive\r\n\r\n", true),
+        ];
+
+        for (request, expected) in cases {
+            let chunk_reader = ChunkReader::new(request, 3);
+            let mut reader = BufReader::new(chunk_reader);
+            let req = Request::req_from_reader(&mut reader).expect("Failed to parse request");
+            assert_eq!(req.keep_alive(), *expected);
+        }
+    }
+
+    fn error_status(result: Result<Request, ParseError>) -> StatusCode {
+        result.expect_err("Expected request to be rejected").status()
+    }
+
+    #[test]
+    fn test_typed_method_and_version() {
+        let request = b"DELETE /item HTTP/1.0\r\nHost: localhost\r\n\r\n";
+        let mut reader = BufReader::new(ChunkReader::new(request, 3));
+        let req = Request::req_from_reader(&mut reader).expect("Failed to parse request");
+        assert_eq!(*req.method(), Method::Delete);
+        assert_eq!(req.http_version(), Version::Http10);
+    }
+
+    #[test]
+    fn test_unknown_method_is_not_implemented() {
+        let request = b"PROPFIND /dav HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut reader = BufReader::new(ChunkReader::new(request, 3));
+        let result = Request::req_from_reader(&mut reader);
+        assert_eq!(error_status(result), StatusCode::NotImplemented);
+    }
+
+    #[test]
+    fn test_registered_extension_method_is_accepted() {
+        let request = b"PROPFIND /dav HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut reader = BufReader::new(ChunkReader::new(request, 3));
+        let options = ParseOptions { extension_methods: vec!["PROPFIND".to_string()], ..Default::default() };
+        let req = Request::req_from_reader_with(&mut reader, &options).expect("Failed to parse request");
+        assert_eq!(*req.method(), Method::Extension("PROPFIND".to_string()));
+        assert_eq!(req.method().as_str(), "PROPFIND");
+    }
+
+    #[test]
+    fn test_lowercase_method_is_distinct_from_get() {
+        assert_eq!("get".parse::<Method>(), Ok(Method::Extension("get".to_string())));
+        assert!("G(T".parse::<Method>().is_err());
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let request = b"GET / HTTP/2.0\r\nHost: localhost\r\n\r\n";
+        let mut reader = BufReader::new(ChunkReader::new(request, 3));
+        let result = Request::req_from_reader(&mut reader);
+        assert_eq!(error_status(result), StatusCode::HttpVersionNotSupported);
+    }
+
+    #[test]
+    fn test_malformed_version_is_bad_request() {
+        for version in ["HTTP/1", "HTTP/1.1.1", "http/1.1", "HTTP/x.y"] {
+            let request = format!("GET / {}\r\nHost: localhost\r\n\r\n", version);
+            let mut reader = BufReader::new(ChunkReader::new(request.as_bytes(), 3));
+            let result = Request::req_from_reader(&mut reader);
+            assert!(matches!(result, Err(ParseError::InvalidVersion)), "{} should be malformed", version);
+        }
+    }
+
+    #[test]
+    fn test_later_minor_version_is_http_11() {
+        assert_eq!(Version::parse("HTTP/1.2"), Ok(Version::Http11));
+    }
+
+    #[test]
+    fn test_parse_errors_are_matchable() {
+        assert!(matches!(
+            parse(b"G(T / HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Err(ParseError::InvalidMethod)
+        ));
+        assert!(matches!(
+            parse(b"BREW / HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Err(ParseError::MethodNotImplemented(m)) if m == "BREW"
+        ));
+        assert!(matches!(
+            parse(b"GET / HTTP/1.1\r\n\r\n"),
+            Err(ParseError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            parse(b"GET / HTTP/1.1\r\nAccept: */*\r\n\r\n"),
+            Err(ParseError::MissingHost)
+        ));
+        assert!(matches!(
+            parse(b"GET nowhere HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Err(ParseError::InvalidTarget)
+        ));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Err(ParseError::ConflictingFraming)
+        ));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"),
+            Err(ParseError::BadChunk(_))
+        ));
+        assert!(matches!(
+            parse(b"GET / HTTP/1.1\r\nHost: a"),
+            Err(ParseError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_status_codes() {
+        assert_eq!(ParseError::UriTooLong.status(), StatusCode::UriTooLong);
+        assert_eq!(ParseError::HeaderTooLarge.status(), StatusCode::RequestHeaderFieldsTooLarge);
+        assert_eq!(ParseError::BodyTooLarge.status(), StatusCode::ContentTooLarge);
+        assert_eq!(ParseError::UnsupportedVersion.status(), StatusCode::HttpVersionNotSupported);
+        assert_eq!(ParseError::MethodNotImplemented("X".into()).status(), StatusCode::NotImplemented);
+        assert_eq!(ParseError::BadChunk("x").status(), StatusCode::BadRequest);
+        let timeout = std::io::Error::new(std::io::ErrorKind::TimedOut, "slow");
+        assert_eq!(ParseError::Io(timeout).status(), StatusCode::RequestTimeout);
+    }
+
+    fn parse_limited(request: &[u8], limits: Limits) -> Result<Request, ParseError> {
+        let mut reader = BufReader::new(ChunkReader::new(request, 3));
+        let options = ParseOptions { limits, ..Default::default() };
+        Request::req_from_reader_with(&mut reader, &options)
+    }
+
+    #[test]
+    fn test_long_request_line_is_uri_too_long() {
+        let limits = Limits { max_request_line: 32, ..Default::default() };
+        let ok = parse_limited(b"GET /short HTTP/1.1\r\nHost: a\r\n\r\n", limits.clone());
+        assert!(ok.is_ok());
+
+        let long = format!("GET /{} HTTP/1.1\r\nHost: a\r\n\r\n", "a".repeat(64));
+        assert_eq!(error_status(parse_limited(long.as_bytes(), limits)), StatusCode::UriTooLong);
+    }
+
+    #[test]
+    fn test_header_limits_are_431() {
+        let limits = Limits { max_header_line: 32, ..Default::default() };
+        let long = format!("GET / HTTP/1.1\r\nHost: a\r\nX-Long: {}\r\n\r\n", "v".repeat(64));
+        assert_eq!(error_status(parse_limited(long.as_bytes(), limits)), StatusCode::RequestHeaderFieldsTooLarge);
+
+        let limits = Limits { max_headers: 2, ..Default::default() };
+        let many = b"GET / HTTP/1.1\r\nHost: a\r\nX-A: 1\r\nX-B: 2\r\n\r\n";
+        assert_eq!(error_status(parse_limited(many, limits)), StatusCode::RequestHeaderFieldsTooLarge);
+
+        let limits = Limits { max_header_bytes: 40, ..Default::default() };
+        let big = b"GET / HTTP/1.1\r\nHost: a\r\nX-A: 0123456789\r\nX-B: 0123456789\r\n\r\n";
+        assert_eq!(error_status(parse_limited(big, limits)), StatusCode::RequestHeaderFieldsTooLarge);
+    }
+
+    #[test]
+    fn test_body_limits_are_413() {
+        let limits = Limits { max_body_size: 4, ..Default::default() };
+        let request = b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello";
+        assert_eq!(error_status(parse_limited(request, limits.clone())), StatusCode::ContentTooLarge);
+
+        let request = b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n";
+        assert_eq!(error_status(parse_limited(request, limits)), StatusCode::ContentTooLarge);
+
+        let limits = Limits { max_chunk_size: 2, ..Default::default() };
+        assert_eq!(error_status(parse_limited(request, limits)), StatusCode::ContentTooLarge);
+
+        let mut req = parse_limited(request, Limits::default()).expect("Failed to parse request");
+        assert_eq!(req.body().bytes().unwrap(), b"abcdef");
+    }
+
+    #[test]
+    fn test_line_without_crlf_is_not_duplicated() {
+        let req = parse(b"GET / HTTP/1.1\r\nHost: localhost\r\nX-Split: abcdefghij\r\n\r\n")
+            .expect("Failed to parse request");
+        assert_eq!(req.header("x-split"), Some("abcdefghij"));
+    }
+
+    #[test]
+    fn test_request_headers_are_shared_type() {
+        let mut req = parse(b"GET / HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: 7\r\n\r\n")
+            .expect("Failed to parse request");
+        assert_eq!(req.headers().original_name("x-request-id"), Some("X-Request-Id"));
+        assert_eq!(req.headers().iter().next(), Some(("Host", "localhost")));
+
+        req.headers_mut().insert("x-request-id", "8");
+        req.headers_mut().remove("host");
+        assert_eq!(req.header("X-Request-Id"), Some("8"));
+        assert_eq!(req.headers().len(), 1);
+    }
+
+    fn chunked(body: &str) -> Vec<u8> {
+        format!("POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n{}", body).into_bytes()
+    }
+
+    #[test]
+    fn test_chunk_extensions_are_parsed_and_ignored() {
+        let body = "5;name=value\r\nhello\r\n6 ; a ; b = \"x;y\\\"z\"\r\n world\r\n0;last\r\n\r\n";
+        let mut req = parse(&chunked(body)).expect("Failed to parse request");
+        assert_eq!(req.body().bytes().unwrap(), b"hello world");
+
+        for size_line in ["5;", "5;=v", "5;a=", "5;a=\"open", "5;a b", "5;a=b c"] {
+            let body = format!("{}\r\nhello\r\n0\r\n\r\n", size_line);
+            assert!(
+                matches!(parse(&chunked(&body)), Err(ParseError::BadChunk(_))),
+                "Size line '{}' should be rejected", size_line,
+            );
+        }
+    }
+
+    #[test]
+    fn test_trailers_are_exposed_after_body() {
+        let body = "5\r\nhello\r\n0\r\nX-Checksum: abc\r\nX-Note: done\r\n\r\n";
+        let mut reader = BufReader::new(ChunkReader::new(&chunked(body), 3));
+        let req = Request::req_from_reader(&mut reader).expect("Failed to parse request");
+
+        let trailers = req.trailers().expect("Expected trailers");
+        assert_eq!(trailers.get_value("x-checksum"), Some("abc"));
+        assert_eq!(trailers.len(), 2);
+
+        // The final CRLF is consumed, so the next request starts cleanly.
+        let mut next = Vec::new();
+        std::io::Read::read_to_end(&mut reader, &mut next).unwrap();
+        assert!(next.is_empty());
+
+        let mut req = parse(&chunked("0\r\n\r\n")).expect("Failed to parse request");
+        assert!(req.trailers().expect("Expected empty trailers").is_empty());
+        assert!(req.body().bytes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_forbidden_trailers_are_rejected() {
+        for field in ["Content-Length: 5", "Transfer-Encoding: chunked", "host: other", "Authorization: x"] {
+            let body = format!("5\r\nhello\r\n0\r\n{}\r\n\r\n", field);
+            let result = parse(&chunked(&body));
+            assert!(matches!(result, Err(ParseError::ForbiddenTrailer(_))), "{} should be rejected", field);
+            assert_eq!(error_status(parse(&chunked(&body))), StatusCode::BadRequest);
+        }
+    }
+
+    #[test]
+    fn test_expects_continue() {
+        let req = parse(b"PUT / HTTP/1.1\r\nHost: a\r\nExpect: 100-Continue\r\nContent-Length: 0\r\n\r\n").unwrap();
+        assert!(req.expects_continue());
+
+        let req = parse(b"PUT / HTTP/1.0\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 0\r\n\r\n").unwrap();
+        assert!(!req.expects_continue(), "HTTP/1.0 clients must not be sent 100 Continue");
+
+        let result = parse(b"PUT / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue, later\r\n\r\n");
+        assert_eq!(error_status(result), StatusCode::ExpectationFailed);
+    }
+
+    #[test]
+    fn test_query_is_split_from_path() {
+        let req = parse(b"GET /search/./items?q=x&page=2 HTTP/1.1\r\nHost: example.com:8080\r\n\r\n").unwrap();
+        assert_eq!(req.path(), "/search/items");
+        assert_eq!(req.query(), Some("q=x&page=2"));
+        assert_eq!(req.path_segments(), vec!["search", "items"]);
+        assert_eq!(req.uri().host(), Some("example.com"));
+        assert_eq!(req.uri().port(), Some(8080));
+    }
+
+    #[test]
+    fn test_unencoded_pipe_in_query_is_accepted() {
+        let req = parse(b"GET /s?q=a|b HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+        assert_eq!(req.query(), Some("q=a|b"));
+        assert_eq!(req.query_params().unwrap().get("q"), Some("a|b"));
+    }
+
+    #[test]
+    fn test_bad_targets_are_bad_requests() {
+        for request in [
+            &b"GET /../secret HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+            b"GET /page#top HTTP/1.1\r\nHost: a\r\n\r\n",
+            b"GET /a%2F..%2Fb HTTP/1.1\r\nHost: a\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: evil/path\r\n\r\n",
+        ] {
+            assert_eq!(error_status(parse(request)), StatusCode::BadRequest);
+        }
+    }
 }
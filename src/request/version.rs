@@ -0,0 +1,54 @@
+use std::fmt;
+
+/// HTTP versions this server speaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+/// Why an `HTTP-version` could not be used.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VersionError {
+    /// Not of the form `HTTP/<digit>.<digit>`.
+    Malformed,
+    /// Well formed but not HTTP/1.x.
+    Unsupported,
+}
+
+impl Version {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+        }
+    }
+
+    /// Parses an `HTTP-version` (RFC 9112 section 2.3). Later HTTP/1.x minor
+    /// versions are treated as HTTP/1.1, which they must stay compatible with.
+    pub fn parse(s: &str) -> Result<Version, VersionError> {
+        let digits = s.strip_prefix("HTTP/").ok_or(VersionError::Malformed)?.as_bytes();
+        match digits {
+            [major, b'.', minor] if major.is_ascii_digit() && minor.is_ascii_digit() => {
+                match (major, minor) {
+                    (b'1', b'0') => Ok(Version::Http10),
+                    (b'1', _) => Ok(Version::Http11),
+                    _ => Err(VersionError::Unsupported),
+                }
+            }
+            _ => Err(VersionError::Malformed),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<&str> for Version {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
@@ -1,94 +1,409 @@
-use std::collections::HashMap;
-use std::io::Write;
+mod status;
+mod test;
 
+use std::io::{self, Write};
+
+use crate::cookie::Cookie;
+use crate::headers::{self, Headers};
+use crate::request::Version;
+
+pub use status::{CustomCode, StatusCode};
+
+#[derive(Debug)]
 pub struct HandlerError {
     pub status: StatusCode,
     pub message: String,
 }
 
-#[derive(Clone)]
-pub enum StatusCode {
-    Ok,
-    NotFound,
-    InternalServerError,
-    BadRequest,
+impl std::fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status, self.message.trim_end())
+    }
 }
 
+impl std::error::Error for HandlerError {}
+
+/// Progress of a response. Status, headers and body are buffered until the
+/// response is finished, so they can still be changed (e.g. by middleware)
+/// up to that point.
 pub enum WriterState {
     Init,
     StatusWritten,
-    HeadersWritten,
     BodyWritten,
+    HeadersWritten,
+    Finished,
+}
+
+/// How the body is delimited once headers are sent.
+enum Framing {
+    /// Body is buffered and sent with a computed Content-Length on `finish`.
+    Buffered,
+    /// Streamed as chunks, ended by a zero-size chunk and the trailers.
+    Chunked,
+    /// Streamed against a Content-Length set up front; holds the bytes left.
+    Length(u64),
+    /// Streamed to an HTTP/1.0 client, ended by closing the connection.
+    UntilClose,
+    /// The status forbids a body, so writes are dropped.
+    NoBody,
 }
 
+/// Builds an HTTP response on top of `inner`. Nothing reaches the stream
+/// until `finish` is called, unless the body is streamed with `write_chunk`;
+/// dropping an unfinished writer finishes it, ignoring errors, the same way
+/// `BufWriter` flushes on drop. A writer dropped while its thread panics is
+/// aborted instead.
 pub struct Writer<'a, W: Write> {
     inner: &'a mut W,
-    headers: HashMap<String, String>,
+    headers: Headers,
+    trailers: Vec<(String, String)>,
     status: Option<StatusCode>,
+    body: Vec<u8>,
     state: WriterState,
+    framing: Framing,
+    version: Version,
+    keep_alive: bool,
+    head: bool,
+    on_headers: Option<Box<dyn FnOnce() + 'a>>,
 }
 
 impl<'a, W: Write> Writer<'a, W> {
     pub fn new(inner: &'a mut W) -> Self {
         Writer {
             inner,
-            headers: HashMap::new(),
+            headers: Headers::new(),
+            trailers: Vec::new(),
             status: None,
+            body: Vec::new(),
             state: WriterState::Init,
+            framing: Framing::Buffered,
+            version: Version::Http11,
+            keep_alive: false,
+            head: false,
+            on_headers: None,
+        }
+    }
+
+    /// Sets the HTTP version of the request being answered. HTTP/1.0 clients
+    /// do not understand chunked encoding, so a streamed body of unknown
+    /// length is sent to them by closing the connection at the end.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    /// Marks whether the connection stays open after this response. Defaults
+    /// to `false`, which sends `Connection: close`.
+    pub fn set_keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// Marks the response as the answer to a HEAD request: the status and
+    /// headers, including the Content-Length a GET would get, are sent as
+    /// usual but body bytes are dropped.
+    pub fn set_head(&mut self, head: bool) {
+        self.head = head;
+    }
+
+    /// Runs `hook` right before the status line goes out, for the server to
+    /// learn that nothing else may be written ahead of the response.
+    pub(crate) fn on_headers(&mut self, hook: impl FnOnce() + 'a) {
+        self.on_headers = Some(Box::new(hook));
+    }
+
+    /// Whether the status and headers have been sent and can no longer be changed.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, WriterState::HeadersWritten | WriterState::Finished)
+    }
+
+    /// Whether a body has been written, even if it has not been sent yet.
+    pub fn has_body(&self) -> bool {
+        !matches!(self.state, WriterState::Init | WriterState::StatusWritten)
+    }
+
+    /// Sends the status line, headers and body. An empty body is sent if the
+    /// handler never wrote one so the client is not left waiting on a
+    /// persistent connection. A streamed body is ended instead: chunked
+    /// bodies get their last chunk and trailers, and a fixed-length body
+    /// that fell short of its Content-Length is an error that also stops the
+    /// connection from being reused. Calling it again does nothing.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if matches!(self.state, WriterState::Finished) {
+            return Ok(());
+        }
+
+        if matches!(self.state, WriterState::HeadersWritten) {
+            self.state = WriterState::Finished;
+            match self.framing {
+                Framing::Chunked => {
+                    let mut end = b"0\r\n".to_vec();
+                    for (k, v) in &self.trailers {
+                        end.extend_from_slice(format!("{}: {}\r\n", k, v).as_bytes());
+                    }
+                    end.extend_from_slice(b"\r\n");
+                    self.inner.write_all(&end)?;
+                }
+                Framing::Length(remaining) if remaining > 0 => {
+                    self.keep_alive = false;
+                    let _ = self.inner.flush();
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Response body is {} bytes short of its Content-Length", remaining),
+                    ));
+                }
+                _ => {}
+            }
+            return self.inner.flush();
+        }
+
+        self.flush_headers(Some(self.body.len()))?;
+        if !self.head && !self.status().forbids_body() {
+            self.inner.write_all(&self.body)?;
+        }
+        self.state = WriterState::Finished;
+        self.inner.flush()
+    }
+
+    /// Gives up on the response: nothing more is sent and the connection is
+    /// not reused, so the client sees a cut-off response rather than one that
+    /// looks complete.
+    pub fn abort(&mut self) {
+        self.keep_alive = false;
+        self.state = WriterState::Finished;
+    }
+
+    /// Sends part of the body right away. The first call sends the status
+    /// and headers: with a Content-Length header already set the body is
+    /// streamed against that length and writing past it is an error,
+    /// otherwise it is sent with chunked transfer encoding. Anything passed
+    /// to `write_body` before goes out first. Call `finish` to end the body.
+    pub fn write_chunk(&mut self, data: &[u8]) -> io::Result<()> {
+        match self.state {
+            WriterState::Finished => {
+                return Err(io::Error::other("Cannot write to a finished response"));
+            }
+            WriterState::HeadersWritten => {}
+            _ => {
+                self.start_streaming()?;
+                let buffered = std::mem::take(&mut self.body);
+                self.write_framed(&buffered)?;
+            }
+        }
+        self.write_framed(data)
+    }
+
+    /// Sets a trailer field sent after the last chunk. Trailers only exist
+    /// in chunked encoding and are dropped for any other response; set them
+    /// before the first chunk to have them announced in a `Trailer` header.
+    pub fn set_trailer(&mut self, key: &str, value: &str) {
+        if matches!(self.state, WriterState::Finished) {
+            panic!("Cannot set trailers after the response is sent");
+        }
+        headers::assert_valid_field(key, value);
+        self.trailers.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        self.trailers.push((key.to_string(), value.to_string()));
+    }
+
+    fn start_streaming(&mut self) -> io::Result<()> {
+        let framing = if self.status().forbids_body() {
+            Framing::NoBody
+        } else if let Some(len) = self.header("Content-Length") {
+            let len = len.trim().parse().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Content-Length header is not a number")
+            })?;
+            Framing::Length(len)
+        } else if self.version == Version::Http10 {
+            self.keep_alive = false;
+            Framing::UntilClose
+        } else {
+            self.set_header("Transfer-Encoding", "chunked");
+            if !self.trailers.is_empty() {
+                let names: Vec<&str> = self.trailers.iter().map(|(k, _)| k.as_str()).collect();
+                let names = names.join(", ");
+                self.set_header("Trailer", &names);
+            }
+            Framing::Chunked
+        };
+        self.flush_headers(None)?;
+        self.framing = if self.head { Framing::NoBody } else { framing };
+        Ok(())
+    }
+
+    fn write_framed(&mut self, data: &[u8]) -> io::Result<()> {
+        match &mut self.framing {
+            Framing::Chunked => {
+                if data.is_empty() {
+                    return Ok(());
+                }
+                self.inner.write_all(format!("{:x}\r\n", data.len()).as_bytes())?;
+                self.inner.write_all(data)?;
+                self.inner.write_all(b"\r\n")
+            }
+            Framing::Length(remaining) => {
+                if data.len() as u64 > *remaining {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Response body is longer than its Content-Length",
+                    ));
+                }
+                *remaining -= data.len() as u64;
+                self.inner.write_all(data)
+            }
+            Framing::UntilClose => self.inner.write_all(data),
+            Framing::Buffered | Framing::NoBody => Ok(()),
+        }
+    }
+
+    fn assert_not_sent(&self, what: &str) {
+        if self.is_finished() {
+            panic!("Cannot set {} after the response is sent", what);
         }
     }
 
     pub fn set_status(&mut self, status: StatusCode) {
+        self.assert_not_sent("status");
+        self.status = Some(status);
         if matches!(self.state, WriterState::Init) {
-            self.status = Some(status);
             self.state = WriterState::StatusWritten;
-        } else {
-            panic!("Cannot set status after writing headers or body");
         }
     }
 
+    /// The status that will be sent, `200 OK` unless one was set.
+    pub fn status(&self) -> &StatusCode {
+        self.status.as_ref().unwrap_or(&StatusCode::Ok)
+    }
+
+    /// Sets a header, replacing any existing header with the same name
+    /// regardless of case. Panics on names and values `Headers` refuses.
     pub fn set_header(&mut self, key: &str, value: &str) {
-        if matches!(self.state, WriterState::StatusWritten) {
-            self.headers.insert(key.to_string(), value.to_string());
-        } else {
-            panic!("Cannot set headers after body is written");
-        }
+        self.assert_not_sent("headers");
+        self.headers.insert(key, value);
+    }
+
+    /// Adds a header line without replacing existing ones with the same
+    /// name, for fields like `Set-Cookie` that are sent once per value.
+    pub fn append_header(&mut self, key: &str, value: &str) {
+        self.assert_not_sent("headers");
+        self.headers.append(key, value);
+    }
+
+    /// Sends `cookie` in a `Set-Cookie` header line of its own.
+    pub fn set_cookie(&mut self, cookie: &Cookie) {
+        self.append_header("Set-Cookie", &cookie.to_string());
+    }
+
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.get_value(key)
+    }
+
+    pub fn remove_header(&mut self, key: &str) {
+        self.assert_not_sent("headers");
+        self.headers.remove(key);
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        self.assert_not_sent("headers");
+        &mut self.headers
     }
 
-    pub fn write_body(&mut self, body: &[u8]) -> std::io::Result<()> {
-        if !matches!(self.state, WriterState::BodyWritten) {
-            self.flush_headers(body.len())?;
-            self.inner.write_all(body)?;
-            self.state = WriterState::BodyWritten;
+    /// Appends to the response body. The body is buffered until `finish`,
+    /// or sent straight away once streaming has started with `write_chunk`.
+    /// Use `set_body` to replace a body that was already written.
+    pub fn write_body(&mut self, body: &[u8]) -> io::Result<()> {
+        if self.is_finished() {
+            return self.write_chunk(body);
         }
+        self.body.extend_from_slice(body);
+        self.state = WriterState::BodyWritten;
         Ok(())
     }
 
-    fn flush_headers(&mut self, content_length: usize) -> std::io::Result<()> {
-        let status_line = match self.status.as_ref().unwrap_or(&StatusCode::Ok) {
-            StatusCode::Ok => "HTTP/1.1 200 OK\r\n",
-            StatusCode::BadRequest => "HTTP/1.1 400 Bad Request\r\n",
-            StatusCode::NotFound => "HTTP/1.1 404 Not Found\r\n",
-            StatusCode::InternalServerError => "HTTP/1.1 500 Internal Server Error\r\n",
-        };
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Replaces the buffered body.
+    pub fn set_body(&mut self, body: Vec<u8>) {
+        self.assert_not_sent("body");
+        self.body = body;
+        self.state = WriterState::BodyWritten;
+    }
+
+    /// Writes the status line and headers, adding a Content-Length when the
+    /// whole body is known.
+    fn flush_headers(&mut self, content_length: Option<usize>) -> io::Result<()> {
+        if let Some(hook) = self.on_headers.take() {
+            hook();
+        }
+        let status_line = format!("HTTP/1.1 {} {}\r\n", self.status().as_u16(), self.status().reason_phrase());
         self.inner.write_all(status_line.as_bytes())?;
 
-        self.headers
-            .entry("Content-Length".to_string())
-            .or_insert(content_length.to_string());
-        self.headers
-            .entry("Content-Type".to_string())
-            .or_insert("text/plain; charset=utf-8".to_string());
-        self.headers
-            .entry("Connection".to_string())
-            .or_insert("close".to_string());
-
-        for (k, v) in &self.headers {
-            let line = format!("{}: {}\r\n", k, v);
-            self.inner.write_all(line.as_bytes())?;
-        }
-        self.inner.write_all(b"\r\n")?;
+        // 1xx, 204 and 304 responses have no body (RFC 9110 section 6.4.1),
+        // so they get neither a made-up length nor a default type.
+        let no_body = self.status().forbids_body();
+        if no_body {
+            self.headers.remove("Content-Length");
+        } else if let Some(len) = content_length
+            && self.header("Content-Length").is_none()
+        {
+            self.headers.insert("Content-Length", &len.to_string());
+        }
+        if !no_body && self.header("Content-Type").is_none() {
+            self.headers.insert("Content-Type", "text/plain; charset=utf-8");
+        }
+
+        let connection = self.header("Connection").map(|v| v.to_string());
+        match connection {
+            Some(value) => {
+                if value.split(',').any(|t| t.trim().eq_ignore_ascii_case("close")) {
+                    self.keep_alive = false;
+                }
+            }
+            None => {
+                let value = if self.keep_alive { "keep-alive" } else { "close" };
+                self.headers.insert("Connection", value);
+            }
+        }
+
+        let mut head = Vec::new();
+        for (k, v) in self.headers.iter() {
+            head.extend_from_slice(format!("{}: {}\r\n", k, v).as_bytes());
+        }
+        head.extend_from_slice(b"\r\n");
+        self.inner.write_all(&head)?;
         self.state = WriterState::HeadersWritten;
         Ok(())
     }
 }
+
+/// Streams the body, so a `Writer` can be the target of `io::copy` or
+/// `write!`.
+impl<W: Write> Write for Writer<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_chunk(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Writer<'_, W> {
+    fn drop(&mut self) {
+        // Finishing after a panic would pass the crash off as a successful
+        // response.
+        if std::thread::panicking() {
+            self.abort();
+            return;
+        }
+        let _ = self.finish();
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+
+/// An unregistered status code, always in `100..=999`. Only made by
+/// `StatusCode::from_u16`, so a `Custom` status is a valid one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CustomCode(u16);
+
+impl CustomCode {
+    pub fn get(&self) -> u16 {
+        self.0
+    }
+}
+
+macro_rules! status_codes {
+    ($($variant:ident = $code:literal, $reason:literal;)+) => {
+        /// HTTP status codes from the IANA registry, plus `Custom` for any
+        /// other three-digit code.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum StatusCode {
+            $($variant,)+
+            /// A code outside the registry, built with `from_u16`. Sent with
+            /// an empty reason phrase.
+            Custom(CustomCode),
+        }
+
+        impl StatusCode {
+            pub fn as_u16(&self) -> u16 {
+                match self {
+                    $(StatusCode::$variant => $code,)+
+                    StatusCode::Custom(code) => code.get(),
+                }
+            }
+
+            /// Canonical reason phrase, empty for `Custom` codes.
+            pub fn reason_phrase(&self) -> &'static str {
+                match self {
+                    $(StatusCode::$variant => $reason,)+
+                    StatusCode::Custom(_) => "",
+                }
+            }
+
+            /// Maps a numeric code to its registered variant, or `Custom` for
+            /// unregistered codes. Returns `None` outside `100..=999`.
+            pub fn from_u16(code: u16) -> Option<StatusCode> {
+                match code {
+                    $($code => Some(StatusCode::$variant),)+
+                    100..=999 => Some(StatusCode::Custom(CustomCode(code))),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Processing = 102, "Processing";
+    EarlyHints = 103, "Early Hints";
+
+    Ok = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NonAuthoritativeInformation = 203, "Non-Authoritative Information";
+    NoContent = 204, "No Content";
+    ResetContent = 205, "Reset Content";
+    PartialContent = 206, "Partial Content";
+    MultiStatus = 207, "Multi-Status";
+    AlreadyReported = 208, "Already Reported";
+    ImUsed = 226, "IM Used";
+
+    MultipleChoices = 300, "Multiple Choices";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    UseProxy = 305, "Use Proxy";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    PaymentRequired = 402, "Payment Required";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    ProxyAuthenticationRequired = 407, "Proxy Authentication Required";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    ContentTooLarge = 413, "Content Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    MisdirectedRequest = 421, "Misdirected Request";
+    UnprocessableContent = 422, "Unprocessable Content";
+    Locked = 423, "Locked";
+    FailedDependency = 424, "Failed Dependency";
+    TooEarly = 425, "Too Early";
+    UpgradeRequired = 426, "Upgrade Required";
+    PreconditionRequired = 428, "Precondition Required";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    UnavailableForLegalReasons = 451, "Unavailable For Legal Reasons";
+
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+    VariantAlsoNegotiates = 506, "Variant Also Negotiates";
+    InsufficientStorage = 507, "Insufficient Storage";
+    LoopDetected = 508, "Loop Detected";
+    NotExtended = 510, "Not Extended";
+    NetworkAuthenticationRequired = 511, "Network Authentication Required";
+}
+
+impl StatusCode {
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
+
+    /// Whether a response with this status must not carry a body
+    /// (1xx, 204 and 304, RFC 9110 section 6.4.1).
+    pub fn forbids_body(&self) -> bool {
+        self.is_informational() || matches!(self, StatusCode::NoContent | StatusCode::NotModified)
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> u16 {
+        status.as_u16()
+    }
+}
+
+impl TryFrom<u16> for StatusCode {
+    type Error = u16;
+
+    /// Fails with the rejected code when it is not three digits.
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        StatusCode::from_u16(code).ok_or(code)
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason_phrase() {
+            "" => write!(f, "{}", self.as_u16()),
+            reason => write!(f, "{} {}", self.as_u16(), reason),
+        }
+    }
+}
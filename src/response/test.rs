@@ -0,0 +1,233 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::panic::{self, AssertUnwindSafe};
+
+    use crate::request::Version;
+    use crate::response::{StatusCode, Writer};
+
+    fn render(status: StatusCode, body: &[u8]) -> String {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_status(status);
+        writer.write_body(body).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_status_round_trips_through_u16() {
+        for code in 100..=999u16 {
+            let status = StatusCode::from_u16(code).unwrap();
+            assert_eq!(status.as_u16(), code);
+            assert_eq!(u16::from(status), code);
+        }
+        assert_eq!(StatusCode::from_u16(201), Some(StatusCode::Created));
+        assert!(matches!(StatusCode::from_u16(599), Some(StatusCode::Custom(c)) if c.get() == 599));
+        assert_eq!(StatusCode::from_u16(99), None);
+        assert_eq!(StatusCode::try_from(1000), Err(1000));
+    }
+
+    #[test]
+    fn test_reason_phrases() {
+        assert_eq!(StatusCode::Ok.reason_phrase(), "OK");
+        assert_eq!(StatusCode::ContentTooLarge.reason_phrase(), "Content Too Large");
+        assert_eq!(StatusCode::TooManyRequests.to_string(), "429 Too Many Requests");
+        assert_eq!(StatusCode::from_u16(299).unwrap().to_string(), "299");
+    }
+
+    #[test]
+    fn test_status_classes() {
+        assert!(StatusCode::Continue.is_informational());
+        assert!(StatusCode::NoContent.is_success());
+        assert!(StatusCode::PermanentRedirect.is_redirect());
+        assert!(StatusCode::Forbidden.is_client_error());
+        assert!(StatusCode::from_u16(599).unwrap().is_server_error());
+        assert!(!StatusCode::NotFound.is_success());
+    }
+
+    #[test]
+    fn test_writer_status_line_uses_reason_phrase() {
+        let res = render(StatusCode::Created, b"made");
+        assert!(res.starts_with("HTTP/1.1 201 Created\r\n"));
+        assert!(res.contains("\r\nContent-Length: 4\r\n"));
+        assert!(res.ends_with("\r\n\r\nmade"));
+
+        let res = render(StatusCode::from_u16(599).unwrap(), b"");
+        assert!(res.starts_with("HTTP/1.1 599 \r\n"));
+    }
+
+    #[test]
+    fn test_no_content_omits_length_and_body() {
+        let res = render(StatusCode::NoContent, b"ignored");
+        assert!(res.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(!res.contains("Content-Length"));
+        assert!(!res.contains("Content-Type"));
+        assert!(res.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_not_modified_omits_length_and_type() {
+        let res = render(StatusCode::NotModified, b"ignored");
+        assert!(res.starts_with("HTTP/1.1 304 Not Modified\r\n"));
+        assert!(!res.contains("Content-Length"), "{}", res);
+        assert!(!res.contains("Content-Type"), "{}", res);
+        assert!(res.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_write_body_appends() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.write_body(b"hello ").unwrap();
+        writer.write_body(b"world").unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nContent-Length: 11\r\n"));
+        assert!(res.ends_with("\r\n\r\nhello world"));
+    }
+
+    #[test]
+    fn test_unknown_length_is_chunked_with_trailers() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_trailer("X-Checksum", "pending");
+        writer.write_body(b"buffered ").unwrap();
+        writer.write_chunk(b"streamed").unwrap();
+        write!(writer, "{}", 42).unwrap();
+        writer.write_chunk(b"").unwrap();
+        writer.set_trailer("X-Checksum", "abc");
+        writer.finish().unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nTransfer-Encoding: chunked\r\n"));
+        assert!(res.contains("\r\nTrailer: X-Checksum\r\n"));
+        assert!(!res.contains("Content-Length"));
+        assert!(res.ends_with("\r\n\r\n9\r\nbuffered \r\n8\r\nstreamed\r\n2\r\n42\r\n0\r\nX-Checksum: abc\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_fixed_length_streaming() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_keep_alive(true);
+        writer.set_header("Content-Length", "6");
+        writer.write_chunk(b"abc").unwrap();
+        assert!(writer.write_chunk(b"defg").is_err(), "Over-write must fail");
+        writer.write_chunk(b"def").unwrap();
+        writer.finish().unwrap();
+        assert!(writer.keep_alive());
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nContent-Length: 6\r\n"));
+        assert!(!res.contains("Transfer-Encoding"));
+        assert!(res.ends_with("\r\n\r\nabcdef"));
+    }
+
+    #[test]
+    fn test_head_keeps_length_but_drops_body() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_head(true);
+        writer.write_body(b"hello").unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nContent-Length: 5\r\n"));
+        assert!(res.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_head_streaming_sends_no_chunks() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_keep_alive(true);
+        writer.set_head(true);
+        writer.write_chunk(b"abc").unwrap();
+        writer.finish().unwrap();
+        assert!(writer.keep_alive());
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nTransfer-Encoding: chunked\r\n"));
+        assert!(res.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_fixed_length_underwrite_fails_and_closes() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_keep_alive(true);
+        writer.set_header("Content-Length", "10");
+        writer.write_chunk(b"short").unwrap();
+        assert!(writer.finish().is_err());
+        assert!(!writer.keep_alive());
+        assert!(writer.write_chunk(b"more").is_err());
+    }
+
+    #[test]
+    fn test_http10_stream_is_delimited_by_close() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_version(Version::Http10);
+        writer.set_keep_alive(true);
+        writer.write_chunk(b"data").unwrap();
+        writer.finish().unwrap();
+        assert!(!writer.keep_alive());
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nConnection: close\r\n"));
+        assert!(!res.contains("Transfer-Encoding"));
+        assert!(res.ends_with("\r\n\r\ndata"));
+    }
+
+    #[test]
+    fn test_appended_headers_are_sent_as_separate_lines() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.append_header("Set-Cookie", "a=1; Path=/");
+        writer.append_header("Set-Cookie", "b=2");
+        writer.finish().unwrap();
+        drop(writer);
+
+        let res = String::from_utf8(out).unwrap();
+        assert!(res.contains("\r\nSet-Cookie: a=1; Path=/\r\nSet-Cookie: b=2\r\n"));
+    }
+
+    #[test]
+    fn test_drop_while_panicking_sends_nothing() {
+        let mut out = Vec::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut writer = Writer::new(&mut out);
+            writer.set_status(StatusCode::Ok);
+            writer.write_body(b"partial").unwrap();
+            panic!("Handler failed");
+        }));
+        assert!(result.is_err());
+        assert!(out.is_empty(), "A crashed handler must not look like a response");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_response_splitting_header_panics() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_header("Location", "/a\r\n\r\n<script>");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_trailer_panics() {
+        let mut out = Vec::new();
+        let mut writer = Writer::new(&mut out);
+        writer.set_trailer("X-Checksum", "1\nX-Other: 2");
+    }
+}
@@ -0,0 +1,245 @@
+mod test;
+
+use std::net::TcpStream;
+
+use crate::request::{Method, Request};
+use crate::response::{HandlerError, StatusCode, Writer};
+use crate::server::{Handler, Service};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    /// Matches every remaining segment, including none.
+    Wildcard(String),
+}
+
+struct Route {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Handler,
+}
+
+/// Path parameters captured by the matched route, in pattern order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Result of looking up a method and path.
+pub(crate) enum Lookup<'a> {
+    Found(&'a Handler, Params),
+    /// The path exists but not for this method; holds the methods it has
+    /// routes for.
+    MethodNotAllowed(Vec<Method>),
+    NotFound,
+}
+
+/// Dispatches requests to handlers by method and path pattern.
+///
+/// Patterns are split on `/`; a segment starting with `:` captures one path
+/// segment and a final segment starting with `*` captures the rest of the
+/// path. When several patterns match, literal segments win over `:params`,
+/// which win over `*wildcards`.
+///
+/// `HEAD` requests fall back to the `GET` route, and `OPTIONS` requests
+/// without a route of their own are answered with the `Allow` header, for a
+/// path or, with `OPTIONS *`, for the whole router.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` for `method` requests matching `pattern`.
+    ///
+    /// Panics if the pattern places a wildcard anywhere but the last segment.
+    pub fn route(mut self, method: Method, pattern: &str, handler: impl Service) -> Self {
+        let segments = Self::parse_pattern(pattern);
+        self.routes.push(Route {
+            method,
+            segments,
+            handler: std::sync::Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn get(self, pattern: &str, handler: impl Service) -> Self {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    pub fn post(self, pattern: &str, handler: impl Service) -> Self {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    pub fn put(self, pattern: &str, handler: impl Service) -> Self {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    pub fn patch(self, pattern: &str, handler: impl Service) -> Self {
+        self.route(Method::Patch, pattern, handler)
+    }
+
+    pub fn delete(self, pattern: &str, handler: impl Service) -> Self {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    fn parse_pattern(pattern: &str) -> Vec<Segment> {
+        let parts: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+        parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                if let Some(name) = part.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = part.strip_prefix('*') {
+                    assert!(i == parts.len() - 1, "Wildcard must be the last segment in {}", pattern);
+                    Segment::Wildcard(name.to_string())
+                } else {
+                    Segment::Literal(part.to_string())
+                }
+            })
+            .collect()
+    }
+
+    /// Matches `path` against a pattern, returning the captured params and a
+    /// specificity score used to rank competing routes.
+    fn match_segments(segments: &[Segment], path: &[&str]) -> Option<(Params, Vec<u8>)> {
+        let mut params = Vec::new();
+        let mut rank = Vec::with_capacity(segments.len());
+
+        for (i, segment) in segments.iter().enumerate() {
+            match segment {
+                Segment::Wildcard(name) => {
+                    params.push((name.clone(), path.get(i..).unwrap_or(&[]).join("/")));
+                    rank.push(0);
+                    return Some((Params(params), rank));
+                }
+                Segment::Literal(lit) => {
+                    if path.get(i) != Some(&lit.as_str()) {
+                        return None;
+                    }
+                    rank.push(2);
+                }
+                Segment::Param(name) => {
+                    params.push((name.clone(), path.get(i)?.to_string()));
+                    rank.push(1);
+                }
+            }
+        }
+
+        if path.len() == segments.len() { Some((Params(params), rank)) } else { None }
+    }
+
+    /// Finds the route for `method` and the decoded path segments of a
+    /// request, as `Request::path_segments` gives them, so params hold the
+    /// decoded text.
+    pub(crate) fn lookup(&self, method: &Method, path: &[&str]) -> Lookup<'_> {
+        let mut best: Option<(&Route, Params, Vec<u8>)> = None;
+        let mut allowed: Vec<Method> = Vec::new();
+
+        for route in &self.routes {
+            let Some((params, rank)) = Self::match_segments(&route.segments, path) else {
+                continue;
+            };
+            if route.method != *method {
+                if !allowed.contains(&route.method) {
+                    allowed.push(route.method.clone());
+                }
+                continue;
+            }
+            if best.as_ref().is_none_or(|(_, _, best_rank)| rank > *best_rank) {
+                best = Some((route, params, rank));
+            }
+        }
+
+        if best.is_none() && *method == Method::Head && allowed.contains(&Method::Get) {
+            return self.lookup(&Method::Get, path);
+        }
+
+        match best {
+            Some((route, params, _)) => Lookup::Found(&route.handler, params),
+            None if !allowed.is_empty() => {
+                allowed.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                Lookup::MethodNotAllowed(allowed)
+            }
+            None => Lookup::NotFound,
+        }
+    }
+
+    /// Value of the `Allow` header for a resource with routes for `methods`,
+    /// counting the `HEAD` and `OPTIONS` support the router adds.
+    fn allow_header(methods: &[Method]) -> String {
+        let mut allowed: Vec<&str> = methods.iter().map(|m| m.as_str()).collect();
+        if methods.contains(&Method::Get) {
+            allowed.push("HEAD");
+        }
+        allowed.push("OPTIONS");
+        allowed.sort_unstable();
+        allowed.dedup();
+        allowed.join(", ")
+    }
+
+    fn write_options(res: &mut Writer<TcpStream>, methods: &[Method]) {
+        res.set_status(StatusCode::NoContent);
+        res.set_header("Allow", &Self::allow_header(methods));
+    }
+}
+
+impl Service for Router {
+    fn call(&self, mut req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        if *req.method() == Method::Options && req.request_target() == "*" {
+            let mut methods: Vec<Method> = Vec::new();
+            for route in &self.routes {
+                if !methods.contains(&route.method) {
+                    methods.push(route.method.clone());
+                }
+            }
+            Self::write_options(res, &methods);
+            return Ok(());
+        }
+
+        let lookup = self.lookup(req.method(), &req.path_segments());
+        match lookup {
+            Lookup::Found(handler, params) => {
+                req.set_params(params);
+                handler.call(req, res)
+            }
+            Lookup::MethodNotAllowed(allowed) if *req.method() == Method::Options => {
+                Self::write_options(res, &allowed);
+                Ok(())
+            }
+            Lookup::MethodNotAllowed(allowed) => {
+                res.set_status(StatusCode::MethodNotAllowed);
+                res.set_header("Allow", &Self::allow_header(&allowed));
+                let _ = res.write_body(b"Method Not Allowed\n");
+                Ok(())
+            }
+            Lookup::NotFound => {
+                res.set_status(StatusCode::NotFound);
+                let _ = res.write_body(b"Not Found\n");
+                Ok(())
+            }
+        }
+    }
+}
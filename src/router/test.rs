@@ -0,0 +1,175 @@
+#[cfg(test)]
+mod tests {
+    use std::net::TcpStream;
+
+    use crate::request::{Method, Request};
+    use crate::response::{HandlerError, StatusCode, Writer};
+    use crate::router::{Lookup, Router};
+    use crate::server::Server;
+    use crate::server::test::roundtrip;
+    use crate::uri::Uri;
+
+    fn ok(_req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        res.set_status(StatusCode::Ok);
+        let _ = res.write_body(b"ok");
+        Ok(())
+    }
+
+    fn lookup<'a>(router: &'a Router, method: &Method, path: &str) -> Lookup<'a> {
+        let uri = Uri::parse(path).unwrap();
+        let segments: Vec<&str> = uri.segments().collect();
+        router.lookup(method, &segments)
+    }
+
+    fn params_of(router: &Router, method: Method, path: &str) -> Vec<(String, String)> {
+        match lookup(router, &method, path) {
+            Lookup::Found(_, params) => params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            Lookup::MethodNotAllowed(_) => panic!("{} {} matched with the wrong method", method, path),
+            Lookup::NotFound => panic!("{} {} did not match", method, path),
+        }
+    }
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_params_and_wildcard() {
+        let router = Router::new().get("/users/:id/files/*rest", ok);
+        assert_eq!(
+            params_of(&router, Method::Get, "/users/42/files/a/b.txt"),
+            pairs(&[("id", "42"), ("rest", "a/b.txt")]),
+        );
+        assert_eq!(
+            params_of(&router, Method::Get, "/users/42/files"),
+            pairs(&[("id", "42"), ("rest", "")]),
+        );
+    }
+
+    #[test]
+    fn test_literal_beats_param_beats_wildcard() {
+        let router = Router::new()
+            .get("/*path", ok)
+            .get("/users/:id", ok)
+            .get("/users/me", ok);
+
+        assert_eq!(params_of(&router, Method::Get, "/users/me"), pairs(&[]));
+        assert_eq!(params_of(&router, Method::Get, "/users/7"), pairs(&[("id", "7")]));
+        assert_eq!(params_of(&router, Method::Get, "/other/x"), pairs(&[("path", "other/x")]));
+    }
+
+    #[test]
+    fn test_unknown_path_is_not_found() {
+        let router = Router::new().get("/users/:id", ok);
+        assert!(matches!(lookup(&router, &Method::Get, "/users"), Lookup::NotFound));
+        assert!(matches!(lookup(&router, &Method::Get, "/users/1/extra"), Lookup::NotFound));
+    }
+
+    #[test]
+    fn test_wrong_method_lists_allowed() {
+        let router = Router::new()
+            .post("/items/:id", ok)
+            .delete("/items/:id", ok)
+            .get("/items", ok);
+
+        match lookup(&router, &Method::Get, "/items/3") {
+            Lookup::MethodNotAllowed(allowed) => assert_eq!(allowed, vec!["DELETE", "POST"]),
+            _ => panic!("Expected 405 lookup"),
+        }
+    }
+
+    #[test]
+    fn test_params_are_percent_decoded() {
+        let router = Router::new().get("/users/:name", ok);
+        assert_eq!(params_of(&router, Method::Get, "/users/j%C3%BCrgen%20k"), pairs(&[("name", "jürgen k")]));
+    }
+
+    #[test]
+    fn test_head_falls_back_to_get() {
+        let router = Router::new().get("/users/:id", ok).post("/users", ok);
+
+        assert_eq!(params_of(&router, Method::Head, "/users/7"), pairs(&[("id", "7")]));
+        match lookup(&router, &Method::Head, "/users") {
+            Lookup::MethodNotAllowed(allowed) => assert_eq!(allowed, vec!["POST"]),
+            _ => panic!("Expected 405 lookup"),
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wildcard_must_be_last() {
+        let _ = Router::new().get("/*rest/tail", ok);
+    }
+
+    fn double(req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        let n: i64 = req.param_as("n")?;
+        res.set_status(StatusCode::Ok);
+        let _ = res.write_body((n * 2).to_string().as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_router_as_server_handler() {
+        let router = Router::new().get("/double/:n", double).post("/double/:n", double);
+        let server = Server::start(0, router).expect("Failed to start server");
+
+        let res = roundtrip(&server, b"GET /double/21 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.ends_with("\r\n\r\n42"));
+
+        let res = roundtrip(&server, b"GET /double/x HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        let res = roundtrip(&server, b"DELETE /double/1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(res.contains("\r\nAllow: GET, HEAD, OPTIONS, POST\r\n"));
+
+        let res = roundtrip(&server, b"GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_head_sends_get_headers_without_body() {
+        let router = Router::new().get("/double/:n", double);
+        let server = Server::start(0, router).expect("Failed to start server");
+        let response = roundtrip(
+            &server,
+            b"HEAD /double/21 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+              GET /double/4 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        );
+
+        // The HEAD response ends right after its headers, so the GET
+        // response follows it directly on the same connection.
+        let (head, get) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("\r\nContent-Length: 2"));
+        assert!(get.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(get.ends_with("\r\n\r\n8"));
+    }
+
+    #[test]
+    fn test_options_lists_allowed_methods() {
+        let router = Router::new()
+            .get("/items/:id", ok)
+            .delete("/items/:id", ok)
+            .post("/orders", ok);
+        let server = Server::start(0, router).expect("Failed to start server");
+
+        let res = roundtrip(&server, b"OPTIONS /items/1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(res.contains("\r\nAllow: DELETE, GET, HEAD, OPTIONS\r\n"));
+
+        let res = roundtrip(&server, b"OPTIONS * HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(res.contains("\r\nAllow: DELETE, GET, HEAD, OPTIONS, POST\r\n"));
+
+        let res = roundtrip(&server, b"OPTIONS /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_explicit_options_route_wins() {
+        let router = Router::new().get("/items", ok).route(Method::Options, "/items", ok);
+        assert!(matches!(lookup(&router, &Method::Options, "/items"), Lookup::Found(..)));
+    }
+}
@@ -1,99 +1,402 @@
-use std::net::{TcpListener, TcpStream};
+mod poll;
+mod pool;
+pub(crate) mod test;
+mod timeout;
+mod tracker;
+
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, Mutex,
 };
-use std::thread;
-use std::io::{BufReader, Write};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use std::io::{BufRead, BufReader};
 use std::error::Error;
+use std::fmt;
 use std::result::Result;
 
-use crate::request::Request;
+use crate::extensions::Extensions;
+use crate::request::{Method, ParseOptions, Request, SharedSource, Source};
+use crate::server::poll::{Event, Poller, Waker};
+use crate::server::pool::ThreadPool;
+use crate::server::timeout::TimedStream;
+use crate::server::tracker::{ConnectionTracker, Tracked};
 use crate::response::{
     HandlerError,
     StatusCode,
     Writer,
 };
 
+pub use crate::server::tracker::ShutdownReport;
+
 pub struct Server {
     listener: TcpListener,
     is_closed: Arc<AtomicBool>,
+    waker: Arc<Waker>,
+    tracker: Arc<ConnectionTracker>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+/// Anything that can answer a request. Implemented for every
+/// `Fn(Request, &mut Writer<TcpStream>) -> Result<(), HandlerError>`, so plain
+/// functions and closures capturing their own state both work as handlers.
+pub trait Service: Send + Sync + 'static {
+    fn call(&self, req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError>;
+}
+
+impl<F> Service for F
+where
+    F: Fn(Request, &mut Writer<TcpStream>) -> Result<(), HandlerError> + Send + Sync + 'static,
+{
+    fn call(&self, req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        self(req, res)
+    }
+}
+
+pub type Handler = Arc<dyn Service>;
+
+/// Decides from the headers alone whether a request sent with
+/// `Expect: 100-continue` may go ahead. An error is sent as the final
+/// response and the body is never read.
+#[derive(Clone)]
+pub struct ContinueCheck(Arc<CheckFn>);
+
+type CheckFn = dyn Fn(&Request) -> Result<(), HandlerError> + Send + Sync;
+
+impl ContinueCheck {
+    pub fn new(check: impl Fn(&Request) -> Result<(), HandlerError> + Send + Sync + 'static) -> Self {
+        ContinueCheck(Arc::new(check))
+    }
+}
+
+impl fmt::Debug for ContinueCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ContinueCheck")
+    }
+}
+
+/// What the accept loop does with a new connection when every worker is busy
+/// and the queue is full.
+#[derive(Clone, Copy, Debug)]
+pub enum Overflow {
+    /// Answer `503 Service Unavailable` with `Retry-After` set to the given
+    /// number of seconds and close the connection.
+    Reject { retry_after: u32 },
+    /// Stop accepting until a slot frees up, leaving new clients in the
+    /// kernel's listen backlog.
+    Block,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Number of worker threads serving connections.
+    pub workers: usize,
+    /// Accepted connections allowed to wait for a free worker.
+    pub queue_capacity: usize,
+    pub overflow: Overflow,
+    /// Application state made available to handlers through `Request::state`.
+    pub state: Extensions,
+    pub parse: ParseOptions,
+    /// Time allowed to receive a request line and headers, counted from
+    /// their first byte. Answered with 408. Also bounds how long a new
+    /// connection may stay silent.
+    pub header_read_timeout: Option<Duration>,
+    /// Time allowed to receive the whole body once the headers are in.
+    /// Answered with 408.
+    pub body_read_timeout: Option<Duration>,
+    /// Time a single write to the client may block before the connection
+    /// is dropped.
+    pub write_timeout: Option<Duration>,
+    /// How long a keep-alive connection may sit idle between requests
+    /// before it is closed.
+    pub keep_alive_timeout: Option<Duration>,
+    /// Run before the handler for requests expecting `100 Continue`. Without
+    /// one, `100 Continue` is sent when the handler first reads the body.
+    pub continue_check: Option<ContinueCheck>,
 }
 
-pub type Handler = fn(req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError>;
+impl Config {
+    /// Registers a value handlers can look up by type with `Request::state`.
+    pub fn with_state<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.state.insert(value);
+        self
+    }
+
+    pub fn with_continue_check(
+        mut self,
+        check: impl Fn(&Request) -> Result<(), HandlerError> + Send + Sync + 'static,
+    ) -> Self {
+        self.continue_check = Some(ContinueCheck::new(check));
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            workers: 16,
+            queue_capacity: 64,
+            overflow: Overflow::Reject { retry_after: 1 },
+            state: Extensions::new(),
+            parse: ParseOptions::default(),
+            header_read_timeout: Some(Duration::from_secs(10)),
+            body_read_timeout: Some(Duration::from_secs(30)),
+            write_timeout: Some(Duration::from_secs(30)),
+            keep_alive_timeout: Some(Duration::from_secs(5)),
+            continue_check: None,
+        }
+    }
+}
 
 impl Drop for Server {
     fn drop(&mut self) {
         self.is_closed.store(true, Ordering::SeqCst);
+        self.waker.wake();
     }
 }
 
 impl Server {
-    pub fn start(port: u16, handler: Handler) -> Result<Self, Box<dyn Error>> {
+    pub fn start(port: u16, handler: impl Service) -> Result<Self, Box<dyn Error>> {
+        Self::start_with_config(port, Config::default(), handler)
+    }
+
+    pub fn start_with_config(port: u16, config: Config, handler: impl Service) -> Result<Self, Box<dyn Error>> {
+        let handler: Handler = Arc::new(handler);
+        if config.workers == 0 {
+            return Err("Server needs at least one worker".into());
+        }
+
         let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let poller = Poller::new(&listener)?;
         let is_closed = Arc::new(AtomicBool::new(false));
-        let server = Server {
+        let tracker = Arc::new(ConnectionTracker::default());
+        let mut server = Server {
             listener: listener.try_clone()?,
             is_closed: is_closed.clone(),
+            waker: poller.waker(),
+            tracker: tracker.clone(),
+            accept_thread: None,
         };
 
-        thread::spawn(move || {
-            Self::listen(listener, is_closed, handler);
-        });
+        server.accept_thread = Some(thread::spawn(move || {
+            Self::listen(listener, poller, is_closed, tracker, config, handler);
+        }));
 
         Ok(server)
     }
 
-    fn listen(listener: TcpListener, is_closed: Arc<AtomicBool>, handler: Handler) {
-        listener.set_nonblocking(true).expect("Failled to set non-blocking");
+    /// Stops accepting connections, closes idle keep-alive connections and
+    /// gives in-flight requests up to `timeout` to finish before their
+    /// sockets are shut down.
+    pub fn shutdown(&mut self, timeout: Duration) -> ShutdownReport {
+        self.is_closed.store(true, Ordering::SeqCst);
+        self.waker.wake();
+        self.tracker.drain(timeout)
+    }
+
+    /// Blocks until the accept loop and every worker thread have exited.
+    /// Call after `shutdown`, otherwise this waits for the server to be
+    /// stopped from elsewhere.
+    pub fn join(mut self) -> thread::Result<()> {
+        match self.accept_thread.take() {
+            Some(handle) => handle.join(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    fn listen(
+        listener: TcpListener,
+        poller: Poller,
+        is_closed: Arc<AtomicBool>,
+        tracker: Arc<ConnectionTracker>,
+        config: Config,
+        handler: Handler,
+    ) {
+        // Dropping the pool at the end of this function waits for the workers,
+        // which is what lets `join` observe a fully stopped server.
+        let overflow = config.overflow;
+        let config = Arc::new(config);
+        let pool = ThreadPool::new(config.workers, config.queue_capacity, move |conn| {
+            if let Some(tracked) = ConnectionTracker::register(&tracker, &conn) {
+                Server::handle(conn, &handler, &config, tracked);
+            }
+        });
 
         while !is_closed.load(Ordering::SeqCst) {
-            match listener.accept() {
-                Ok((conn, _addr)) => {
-                    thread::spawn(move || {
-                        Server::handle(conn, handler);
-                    });
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    thread::sleep(std::time::Duration::from_millis(100));
-                    continue;
-                }
+            match poller.wait() {
+                Ok(Event::Readable) => {}
+                Ok(Event::Woken) => continue,
                 Err(e) => {
-                    eprint!("Listener error: {}", e);
+                    eprintln!("Poller error: {}", e);
                     break;
                 }
             }
+
+            // Accept until the backlog is empty so one wake-up handles a burst
+            // of clients.
+            loop {
+                match listener.accept() {
+                    Ok((conn, _addr)) => {
+                        // Accepted sockets inherit non-blocking mode on some platforms.
+                        if conn.set_nonblocking(false).is_err() {
+                            continue;
+                        }
+                        match overflow {
+                            Overflow::Block => pool.execute(conn),
+                            Overflow::Reject { retry_after } => {
+                                if let Err(conn) = pool.try_execute(conn) {
+                                    Self::reject(conn, retry_after);
+                                }
+                            }
+                        }
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        eprintln!("Listener error: {}", e);
+                        break;
+                    }
+                }
+            }
         }
     }
 
-    fn handle(mut conn: TcpStream, handler: Handler) {
-        let mut reader = BufReader::new(&mut conn);
-        let req = match Request::req_from_reader(&mut reader) {
-            Ok(r) => r,
-            Err(e) => {
-                let mut writer = Writer::new(&mut conn);
-                let err = HandlerError {
-                    status: StatusCode::BadRequest,
-                    message: format!("Failed to parse request: {}\n", e),
-                };
-
-                Self::write_handler_error(&mut writer, err);
+    fn handle(mut conn: TcpStream, handler: &Handler, config: &Config, tracked: Tracked) {
+        // One clone to read from, one for `100 Continue` while the handler
+        // holds the writer.
+        let (read_half, interim_half) = match (conn.try_clone(), conn.try_clone()) {
+            (Ok(read_half), Ok(interim_half)) => (read_half, interim_half),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Failed to clone connection: {}", e);
                 return;
             }
         };
+        if conn.set_write_timeout(config.write_timeout).is_err() {
+            return;
+        }
+        let source = Arc::new(Mutex::new(Source::new(BufReader::new(TimedStream::new(read_half)))));
+        let shared: SharedSource = source.clone();
+        let mut timeout = config.header_read_timeout;
 
-        drop(reader);
+        loop {
+            let parsed = {
+                let mut source = source.lock().unwrap_or_else(|e| e.into_inner());
+                let reader = &mut source.reader;
+                reader.get_mut().set_timeout(timeout);
 
-        let mut writer = Writer::new(&mut conn);
-        if let Err(e) = handler(req, &mut writer) {
-            Self::write_handler_error(&mut writer, e);
+                // A clean EOF between requests means the client closed the
+                // connection; an idle timeout closes it without a response.
+                match reader.fill_buf() {
+                    Ok([]) => return,
+                    Ok(_) => {}
+                    Err(_) => return,
+                }
+
+                if !tracked.set_busy() {
+                    return;
+                }
+
+                reader.get_mut().set_timeout(config.header_read_timeout);
+                let head = Request::read_head(reader, &config.parse);
+                reader.get_mut().set_timeout(config.body_read_timeout);
+                head
+            };
+            let parsed = parsed.and_then(|mut req| {
+                req.stream_body(&shared, &config.parse)?;
+                Ok(req)
+            });
+
+            let mut req = match parsed {
+                Ok(r) => r,
+                Err(e) => {
+                    let mut writer = Writer::new(&mut conn);
+                    let err = HandlerError {
+                        status: e.status(),
+                        message: format!("Failed to parse request: {}\n", e),
+                    };
+
+                    Self::write_handler_error(&mut writer, err);
+                    let _ = writer.finish();
+                    return;
+                }
+            };
+
+            *req.extensions_mut() = config.state.clone();
+
+            let mut writer = Writer::new(&mut conn);
+            writer.set_version(req.http_version());
+            writer.set_keep_alive(req.keep_alive() && !tracked.is_draining());
+            writer.set_head(*req.method() == Method::Head);
+
+            if req.expects_continue() {
+                if let Some(ContinueCheck(check)) = &config.continue_check
+                    && let Err(e) = check(&req)
+                {
+                    // The client may still send the body, so the connection
+                    // cannot be reused.
+                    writer.set_keep_alive(false);
+                    Self::write_handler_error(&mut writer, e);
+                    let _ = writer.finish();
+                    return;
+                }
+                match interim_half.try_clone() {
+                    Ok(interim) => source.lock().unwrap_or_else(|e| e.into_inner()).expect_continue(Box::new(interim)),
+                    Err(_) => return,
+                }
+                let source = source.clone();
+                writer.on_headers(move || source.lock().unwrap_or_else(|e| e.into_inner()).start_response());
+            }
+
+            if let Err(e) = handler.call(req, &mut writer) {
+                Self::write_handler_error(&mut writer, e);
+            }
+
+            if source.lock().unwrap_or_else(|e| e.into_inner()).continue_pending() {
+                writer.set_keep_alive(false);
+            }
+
+            if writer.finish().is_err() || !writer.keep_alive() {
+                return;
+            }
+
+            // Whatever the handler left unread has to go before the next
+            // request can be parsed.
+            let drained = source.lock().unwrap_or_else(|e| e.into_inner()).finish_body();
+            if drained.is_err() || !tracked.set_idle() {
+                return;
+            }
+            timeout = config.keep_alive_timeout;
         }
     }
 
+    fn reject(mut conn: TcpStream, retry_after: u32) {
+        let mut writer = Writer::new(&mut conn);
+        writer.set_status(StatusCode::ServiceUnavailable);
+        writer.set_header("Retry-After", &retry_after.to_string());
+        let _ = writer.write_body(b"Server is busy, try again later\n");
+        let _ = writer.finish();
+    }
+
     fn write_handler_error(writer: &mut Writer<TcpStream>, err: HandlerError) {
+        // Too late to report the error once the response has gone out, but
+        // cutting it off at least keeps the client from taking a truncated
+        // body for a complete one.
+        if writer.is_finished() {
+            writer.abort();
+            return;
+        }
+        // Framing the handler set up was meant for its own body, not this one.
+        for framing in ["Content-Length", "Transfer-Encoding", "Trailer"] {
+            writer.remove_header(framing);
+        }
         writer.set_status(err.status);
         writer.set_header("Content-Type", "text/html");
-        let body = err.message.as_bytes();
-        let _ = writer.write_body(body);
+        writer.set_body(err.message.into_bytes());
     }
 }
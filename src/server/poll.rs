@@ -0,0 +1,162 @@
+//! Readiness notification for the accept loop. On Linux the listener and a
+//! wake-up `eventfd` are registered with epoll so the loop sleeps until a
+//! client connects or the server is asked to stop. Other platforms fall back
+//! to polling the non-blocking listener.
+
+pub enum Event {
+    /// The listener may have connections waiting to be accepted.
+    Readable,
+    /// `Waker::wake` was called.
+    Woken,
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::net::TcpListener;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::sync::Arc;
+
+    use super::Event;
+
+    const EPOLL_CLOEXEC: i32 = 0o2000000;
+    const EPOLL_CTL_ADD: i32 = 1;
+    const EPOLLIN: u32 = 0x1;
+    const EFD_CLOEXEC: i32 = 0o2000000;
+    const EFD_NONBLOCK: i32 = 0o4000;
+
+    const LISTENER_TOKEN: u64 = 0;
+    const WAKER_TOKEN: u64 = 1;
+
+    // The kernel ABI packs this struct on x86_64 only.
+    #[cfg_attr(target_arch = "x86_64", repr(C, packed))]
+    #[cfg_attr(not(target_arch = "x86_64"), repr(C))]
+    #[derive(Clone, Copy)]
+    struct EpollEvent {
+        events: u32,
+        data: u64,
+    }
+
+    unsafe extern "C" {
+        fn epoll_create1(flags: i32) -> i32;
+        fn epoll_ctl(epfd: i32, op: i32, fd: i32, event: *mut EpollEvent) -> i32;
+        fn epoll_wait(epfd: i32, events: *mut EpollEvent, maxevents: i32, timeout: i32) -> i32;
+        fn eventfd(initval: u32, flags: i32) -> i32;
+    }
+
+    fn cvt(ret: i32) -> io::Result<i32> {
+        if ret < 0 { Err(io::Error::last_os_error()) } else { Ok(ret) }
+    }
+
+    pub struct Waker {
+        fd: File,
+    }
+
+    impl Waker {
+        pub fn wake(&self) {
+            // An eventfd counter only fails to accept a write when it would
+            // overflow, in which case a wake-up is already pending.
+            let _ = (&self.fd).write(&1u64.to_ne_bytes());
+        }
+
+        fn reset(&self) {
+            let mut buf = [0u8; 8];
+            let _ = (&self.fd).read(&mut buf);
+        }
+    }
+
+    pub struct Poller {
+        epoll: OwnedFd,
+        waker: Arc<Waker>,
+    }
+
+    impl Poller {
+        /// Watches `listener`, which must already be in non-blocking mode.
+        pub fn new(listener: &TcpListener) -> io::Result<Self> {
+            // SAFETY: each fd is checked by `cvt` and owned by exactly one wrapper.
+            let epoll = unsafe { OwnedFd::from_raw_fd(cvt(epoll_create1(EPOLL_CLOEXEC))?) };
+            let event_fd = unsafe { OwnedFd::from_raw_fd(cvt(eventfd(0, EFD_CLOEXEC | EFD_NONBLOCK))?) };
+
+            Self::register(&epoll, listener.as_raw_fd(), LISTENER_TOKEN)?;
+            Self::register(&epoll, event_fd.as_raw_fd(), WAKER_TOKEN)?;
+
+            Ok(Poller {
+                epoll,
+                waker: Arc::new(Waker { fd: File::from(event_fd) }),
+            })
+        }
+
+        fn register(epoll: &OwnedFd, fd: i32, token: u64) -> io::Result<()> {
+            let mut event = EpollEvent { events: EPOLLIN, data: token };
+            // SAFETY: `event` outlives the call and both fds are open.
+            cvt(unsafe { epoll_ctl(epoll.as_raw_fd(), EPOLL_CTL_ADD, fd, &mut event) })?;
+            Ok(())
+        }
+
+        pub fn waker(&self) -> Arc<Waker> {
+            self.waker.clone()
+        }
+
+        /// Blocks until the listener is readable or the waker fires.
+        pub fn wait(&self) -> io::Result<Event> {
+            let mut events = [EpollEvent { events: 0, data: 0 }; 2];
+            loop {
+                // SAFETY: `events` is valid for `events.len()` entries.
+                let n = unsafe {
+                    epoll_wait(self.epoll.as_raw_fd(), events.as_mut_ptr(), events.len() as i32, -1)
+                };
+                match cvt(n) {
+                    Ok(0) => continue,
+                    Ok(n) => {
+                        if events[..n as usize].iter().any(|e| e.data == WAKER_TOKEN) {
+                            self.waker.reset();
+                            return Ok(Event::Woken);
+                        }
+                        return Ok(Event::Readable);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::Event;
+
+    pub struct Waker;
+
+    impl Waker {
+        pub fn wake(&self) {}
+    }
+
+    pub struct Poller {
+        waker: Arc<Waker>,
+    }
+
+    impl Poller {
+        pub fn new(_listener: &TcpListener) -> io::Result<Self> {
+            Ok(Poller { waker: Arc::new(Waker) })
+        }
+
+        pub fn waker(&self) -> Arc<Waker> {
+            self.waker.clone()
+        }
+
+        pub fn wait(&self) -> io::Result<Event> {
+            thread::sleep(Duration::from_millis(100));
+            Ok(Event::Readable)
+        }
+    }
+}
+
+pub use sys::{Poller, Waker};
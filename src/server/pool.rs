@@ -0,0 +1,81 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Fixed-size set of worker threads that run `work` on every item pushed
+/// through a bounded queue.
+pub struct ThreadPool<T: Send + 'static> {
+    sender: Option<SyncSender<T>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> ThreadPool<T> {
+    pub fn new<F>(size: usize, queue_capacity: usize, work: F) -> Self
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        assert!(size > 0, "Thread pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::sync_channel::<T>(queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let work = Arc::new(work);
+        let workers = (0..size)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let work = work.clone();
+                thread::spawn(move || Self::run(receiver, work))
+            })
+            .collect();
+
+        ThreadPool {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    fn run<F: Fn(T)>(receiver: Arc<Mutex<Receiver<T>>>, work: Arc<F>) {
+        loop {
+            // The lock is released before `work` runs so other workers can pick up items.
+            let item = match receiver.lock() {
+                Ok(rx) => rx.recv(),
+                Err(_) => return,
+            };
+            match item {
+                Ok(item) => {
+                    // A panicking job must not take the worker down with it,
+                    // or the pool shrinks until nothing is left to serve.
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| work(item)));
+                }
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Queues an item, blocking while the queue is full.
+    pub fn execute(&self, item: T) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(item);
+        }
+    }
+
+    /// Queues an item, handing it back if the queue is full.
+    pub fn try_execute(&self, item: T) -> Result<(), T> {
+        match &self.sender {
+            Some(sender) => sender.try_send(item).map_err(|e| match e {
+                TrySendError::Full(item) | TrySendError::Disconnected(item) => item,
+            }),
+            None => Err(item),
+        }
+    }
+}
+
+impl<T: Send + 'static> Drop for ThreadPool<T> {
+    fn drop(&mut self) {
+        // Closing the channel lets workers exit once the queue is drained.
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
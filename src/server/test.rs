@@ -0,0 +1,660 @@
+/// Sends `request` on a new connection and reads until the server closes it.
+#[cfg(test)]
+pub(crate) fn roundtrip(server: &crate::server::Server, request: &[u8]) -> String {
+    use std::io::{Read, Write};
+
+    let port = server.local_addr().unwrap().port();
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).expect("Failed to connect");
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    stream.write_all(request).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use crate::request::{Limits, ParseOptions, Request};
+    use crate::response::{HandlerError, StatusCode, Writer};
+    use crate::server::{Config, Overflow, Server, ShutdownReport};
+
+    fn echo_path(req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        res.set_status(StatusCode::Ok);
+        res.set_header("Content-Type", "text/plain");
+        let _ = res.write_body(req.path().as_bytes());
+        Ok(())
+    }
+
+    fn slow(_req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        std::thread::sleep(Duration::from_millis(300));
+        res.set_status(StatusCode::Ok);
+        let _ = res.write_body(b"done");
+        Ok(())
+    }
+
+    fn connect(server: &Server) -> TcpStream {
+        let port = server.local_addr().unwrap().port();
+        let stream = TcpStream::connect(("127.0.0.1", port)).expect("Failed to connect");
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream
+    }
+
+    /// Reads one response and returns its status line, lowercased headers and body.
+    fn read_response(reader: &mut impl BufRead) -> (String, Vec<(String, String)>, Vec<u8>) {
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line).unwrap();
+
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" || line.is_empty() { break; }
+            let (k, v) = line.trim_end().split_once(':').unwrap();
+            headers.push((k.to_ascii_lowercase(), v.trim().to_string()));
+        }
+
+        let len = headers.iter()
+            .find(|(k, _)| k == "content-length")
+            .map(|(_, v)| v.parse::<usize>().unwrap())
+            .unwrap_or(0);
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).unwrap();
+
+        (status_line.trim_end().to_string(), headers, body)
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        headers.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_keep_alive_serves_multiple_requests() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"GET /first HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let (status, headers, body) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(header(&headers, "connection"), Some("keep-alive"));
+        assert_eq!(body, b"/first");
+
+        stream.write_all(b"GET /second HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let (_, headers, body) = read_response(&mut reader);
+        assert_eq!(header(&headers, "connection"), Some("close"));
+        assert_eq!(body, b"/second");
+
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0, "Connection should be closed");
+    }
+
+    #[test]
+    fn test_http_10_closes_by_default() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"GET /old HTTP/1.0\r\nHost: localhost\r\n\r\n").unwrap();
+        let (_, headers, body) = read_response(&mut reader);
+        assert_eq!(header(&headers, "connection"), Some("close"));
+        assert_eq!(body, b"/old");
+
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0, "Connection should be closed");
+    }
+
+    #[test]
+    fn test_http_10_keep_alive_is_honored() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        for path in ["/a", "/b"] {
+            let req = format!("GET {} HTTP/1.0\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n", path);
+            stream.write_all(req.as_bytes()).unwrap();
+            let (_, headers, body) = read_response(&mut reader);
+            assert_eq!(header(&headers, "connection"), Some("keep-alive"));
+            assert_eq!(body, path.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_full_queue_rejects_with_retry_after() {
+        let config = Config {
+            workers: 1,
+            queue_capacity: 0,
+            overflow: Overflow::Reject { retry_after: 7 },
+            ..Config::default()
+        };
+        let server = Server::start_with_config(0, config, slow).expect("Failed to start server");
+        // Let the lone worker reach the queue, otherwise the first client is the one rejected.
+        std::thread::sleep(Duration::from_millis(50));
+
+        let mut busy = connect(&server);
+        busy.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        std::thread::sleep(Duration::from_millis(150));
+
+        let mut stream = connect(&server);
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(stream);
+        let (status, headers, _) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(header(&headers, "retry-after"), Some("7"));
+
+        let (status, _, body) = read_response(&mut BufReader::new(busy));
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, b"done");
+    }
+
+    #[test]
+    fn test_full_queue_blocks_until_worker_frees() {
+        let config = Config {
+            workers: 1,
+            queue_capacity: 0,
+            overflow: Overflow::Block,
+            ..Config::default()
+        };
+        let server = Server::start_with_config(0, config, slow).expect("Failed to start server");
+
+        let mut streams: Vec<TcpStream> = (0..2).map(|_| connect(&server)).collect();
+        for stream in &mut streams {
+            stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        }
+
+        for stream in streams {
+            let (status, _, body) = read_response(&mut BufReader::new(stream));
+            assert_eq!(status, "HTTP/1.1 200 OK");
+            assert_eq!(body, b"done");
+        }
+    }
+
+    #[test]
+    fn test_panicking_handler_does_not_kill_worker() {
+        let handler = |req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            if req.path() == "/panic" {
+                panic!("Handler failed");
+            }
+            echo_path(req, res)
+        };
+        let config = Config { workers: 1, ..Config::default() };
+        let server = Server::start_with_config(0, config, handler).expect("Failed to start server");
+
+        let mut stream = connect(&server);
+        stream.write_all(b"GET /panic HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut rest = Vec::new();
+        let _ = stream.read_to_end(&mut rest);
+        assert!(rest.is_empty(), "A panic should close the connection without a response");
+
+        let mut stream = connect(&server);
+        stream.write_all(b"GET /after HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let (status, _, body) = read_response(&mut BufReader::new(stream));
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, b"/after");
+    }
+
+    #[test]
+    fn test_zero_workers_is_rejected() {
+        let config = Config { workers: 0, ..Config::default() };
+        assert!(Server::start_with_config(0, config, echo_path).is_err());
+    }
+
+    #[test]
+    fn test_drop_stops_accept_loop() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let port = server.local_addr().unwrap().port();
+        drop(server);
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert!(
+            TcpStream::connect(("127.0.0.1", port)).is_err(),
+            "Listener should be closed once the accept loop exits"
+        );
+    }
+
+    #[test]
+    fn test_shutdown_closes_idle_keep_alive_connections() {
+        let mut server = Server::start(0, echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"GET /idle HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let (_, _, body) = read_response(&mut reader);
+        assert_eq!(body, b"/idle");
+
+        let report = server.shutdown(Duration::from_secs(1));
+        assert_eq!(report, ShutdownReport { drained: 0, idle_closed: 1, forced: 0 });
+
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0, "Idle connection should be closed");
+        server.join().expect("Server threads should exit cleanly");
+    }
+
+    #[test]
+    fn test_shutdown_drains_in_flight_requests() {
+        let mut server = Server::start(0, slow).expect("Failed to start server");
+        let mut stream = connect(&server);
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        let report = server.shutdown(Duration::from_secs(2));
+        assert_eq!(report, ShutdownReport { drained: 1, idle_closed: 0, forced: 0 });
+
+        let mut reader = BufReader::new(stream);
+        let (status, _, body) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, b"done");
+
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0, "Drained connection should be closed");
+        server.join().expect("Server threads should exit cleanly");
+    }
+
+    #[test]
+    fn test_shutdown_forces_requests_past_timeout() {
+        let mut server = Server::start(0, slow).expect("Failed to start server");
+        let mut stream = connect(&server);
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        let report = server.shutdown(Duration::from_millis(20));
+        assert_eq!(report, ShutdownReport { drained: 0, idle_closed: 0, forced: 1 });
+
+        let mut rest = Vec::new();
+        assert_eq!(stream.read_to_end(&mut rest).unwrap_or(0), 0, "Forced connection gets no response");
+        server.join().expect("Server threads should exit cleanly");
+    }
+
+    #[test]
+    fn test_closure_handler_captures_state() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let server = Server::start(0, move |_req: Request, res: &mut Writer<TcpStream>| {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            res.set_status(StatusCode::Ok);
+            let _ = res.write_body(n.to_string().as_bytes());
+            Ok(())
+        }).expect("Failed to start server");
+
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        for expected in ["1", "2"] {
+            stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let (_, _, body) = read_response(&mut reader);
+            assert_eq!(body, expected.as_bytes());
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    struct Greeting(&'static str);
+
+    fn greet(req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        let greeting = req.state::<Greeting>().ok_or_else(|| HandlerError {
+            status: StatusCode::InternalServerError,
+            message: "Missing greeting".to_string(),
+        })?;
+        res.set_status(StatusCode::Ok);
+        let _ = res.write_body(greeting.0.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_state_reaches_handlers() {
+        let config = Config::default().with_state(Greeting("hello"));
+        let server = Server::start_with_config(0, config, greet).expect("Failed to start server");
+
+        let mut stream = connect(&server);
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let (_, _, body) = read_response(&mut BufReader::new(stream));
+        assert_eq!(body, b"hello");
+    }
+
+    #[test]
+    fn test_parse_errors_map_to_status() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let cases: &[(&[u8], &str)] = &[
+            (b"GET / HTTP/2.0\r\nHost: localhost\r\n\r\n", "HTTP/1.1 505 HTTP Version Not Supported"),
+            (b"BREW / HTTP/1.1\r\nHost: localhost\r\n\r\n", "HTTP/1.1 501 Not Implemented"),
+            (b"GET / HTTP/1.1\r\nBad Header: x\r\n\r\n", "HTTP/1.1 400 Bad Request"),
+        ];
+
+        for (request, expected) in cases {
+            let mut stream = connect(&server);
+            stream.write_all(request).unwrap();
+            let (status, headers, _) = read_response(&mut BufReader::new(stream));
+            assert_eq!(status, *expected);
+            assert_eq!(header(&headers, "connection"), Some("close"));
+        }
+    }
+
+    #[test]
+    fn test_handler_error_drops_framing_headers() {
+        let handler = |_req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            res.set_header("Content-Length", "1000");
+            res.set_header("Trailer", "X-Checksum");
+            Err(HandlerError {
+                status: StatusCode::InternalServerError,
+                message: "boom\n".to_string(),
+            })
+        };
+        let server = Server::start(0, handler).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        for _ in 0..2 {
+            stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let (status, headers, body) = read_response(&mut reader);
+            assert_eq!(status, "HTTP/1.1 500 Internal Server Error");
+            assert_eq!(header(&headers, "content-length"), Some("5"));
+            assert_eq!(header(&headers, "trailer"), None);
+            assert_eq!(body, b"boom\n");
+        }
+    }
+
+    fn timeout_config() -> Config {
+        Config {
+            header_read_timeout: Some(Duration::from_millis(200)),
+            body_read_timeout: Some(Duration::from_millis(200)),
+            keep_alive_timeout: Some(Duration::from_millis(200)),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_trickled_headers_time_out_with_408() {
+        let server = Server::start_with_config(0, timeout_config(), echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+
+        // Each byte arrives well within the deadline, but the head as a whole
+        // does not. Stop as soon as the server answers so no write races its close.
+        stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+        let head = b"GET / HTTP/1.1\r\nHost: localhost\r\nX-Slow: ".iter().chain([b'a'; 100].iter());
+        for byte in head {
+            if stream.peek(&mut [0]).is_ok() || stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+        }
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let (status, headers, _) = read_response(&mut BufReader::new(stream));
+        assert_eq!(status, "HTTP/1.1 408 Request Timeout");
+        assert_eq!(header(&headers, "connection"), Some("close"));
+    }
+
+    #[test]
+    fn test_stalled_body_times_out_with_408() {
+        let server = Server::start_with_config(0, timeout_config(), body_len).expect("Failed to start server");
+        let mut stream = connect(&server);
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc").unwrap();
+
+        let (status, _, _) = read_response(&mut BufReader::new(stream));
+        assert_eq!(status, "HTTP/1.1 408 Request Timeout");
+    }
+
+    #[test]
+    fn test_idle_keep_alive_connection_is_closed() {
+        let server = Server::start_with_config(0, timeout_config(), echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"GET /one HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let (status, _, _) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0, "Idle connection should be closed");
+        assert!(rest.is_empty(), "No response is sent for an idle timeout");
+    }
+
+    fn body_len(mut req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        let body = req.body().bytes()?;
+        res.set_status(StatusCode::Ok);
+        let _ = res.write_body(body.len().to_string().as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_handler_streams_body() {
+        let handler = |mut req: Request, res: &mut Writer<TcpStream>| {
+            let mut chunks = Vec::new();
+            let mut buf = [0u8; 4];
+            loop {
+                let n = req.body().read(&mut buf).map_err(|e| HandlerError {
+                    status: StatusCode::BadRequest,
+                    message: e.to_string(),
+                })?;
+                if n == 0 { break; }
+                chunks.push(String::from_utf8_lossy(&buf[..n]).into_owned());
+            }
+            res.set_status(StatusCode::Ok);
+            let _ = res.write_body(chunks.join("|").as_bytes());
+            Ok(())
+        };
+        let server = Server::start(0, handler).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n").unwrap();
+        stream.write_all(b"6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n").unwrap();
+        let (status, _, body) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, b"hell|o |worl|d");
+
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nabc").unwrap();
+        let (_, _, body) = read_response(&mut reader);
+        assert_eq!(body, b"abc");
+    }
+
+    #[test]
+    fn test_unread_body_is_drained_before_next_request() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"POST /first HTTP/1.1\r\nHost: localhost\r\nContent-Length: 12\r\n\r\nignored body").unwrap();
+        stream.write_all(b"GET /second HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let (_, _, body) = read_response(&mut reader);
+        assert_eq!(body, b"/first");
+        let (status, _, body) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, b"/second");
+    }
+
+    #[test]
+    fn test_unread_chunked_body_is_drained() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"POST /first HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\nX-Trailer: 1\r\n\r\n").unwrap();
+        stream.write_all(b"GET /second HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let (_, _, body) = read_response(&mut reader);
+        assert_eq!(body, b"/first");
+        let (status, _, body) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body, b"/second");
+    }
+
+    #[test]
+    fn test_streamed_body_over_limit_is_413() {
+        let config = Config {
+            parse: ParseOptions { limits: Limits { max_body_size: 8, ..Default::default() }, ..Default::default() },
+            ..Config::default()
+        };
+        let server = Server::start_with_config(0, config, body_len).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nabcde\r\n").unwrap();
+        stream.write_all(b"5\r\nfghij\r\n0\r\n\r\n").unwrap();
+        let (status, _, _) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 413 Content Too Large");
+
+        let mut rest = Vec::new();
+        assert_eq!(reader.read_to_end(&mut rest).unwrap(), 0, "Connection should be closed");
+    }
+
+    #[test]
+    fn test_chunked_response_keeps_connection_usable() {
+        let handler = |req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            for part in req.path_segments() {
+                let _ = res.write_chunk(part.as_bytes());
+            }
+            Ok(())
+        };
+        let server = Server::start(0, handler).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        for _ in 0..2 {
+            stream.write_all(b"GET /ab/cde HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let (status, headers, _) = read_response(&mut reader);
+            assert_eq!(status, "HTTP/1.1 200 OK");
+            assert_eq!(header(&headers, "transfer-encoding"), Some("chunked"));
+
+            let mut body = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" { break; }
+                body.push_str(&line);
+            }
+            assert_eq!(body, "2\r\nab\r\n3\r\ncde\r\n0\r\n");
+        }
+    }
+
+    #[test]
+    fn test_handler_error_after_streaming_cuts_response_off() {
+        let handler = |_req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            let _ = res.write_chunk(b"partial");
+            Err(HandlerError {
+                status: StatusCode::InternalServerError,
+                message: "boom\n".to_string(),
+            })
+        };
+        let server = Server::start(0, handler).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let (status, headers, _) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(header(&headers, "transfer-encoding"), Some("chunked"));
+
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "7\r\npartial\r\n", "No last chunk, and the connection is closed");
+    }
+
+    #[test]
+    fn test_streamed_body_trailers_reach_handler() {
+        let handler = |mut req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            assert!(req.trailers().is_none(), "Trailers are unknown before the body is read");
+            let body = req.body().bytes()?;
+            let checksum = req.trailers().and_then(|t| t.get_value("X-Checksum")).unwrap_or("none");
+            let _ = res.write_body(format!("{}:{}", body.len(), checksum).as_bytes());
+            Ok(())
+        };
+        let server = Server::start(0, handler).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n").unwrap();
+        stream.write_all(b"4;ext=1\r\ndata\r\n0\r\nX-Checksum: 8d77\r\n\r\n").unwrap();
+        let (_, _, body) = read_response(&mut reader);
+        assert_eq!(body, b"4:8d77");
+    }
+
+    #[test]
+    fn test_continue_is_sent_when_handler_reads_body() {
+        let server = Server::start(0, body_len).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n").unwrap();
+        let mut interim = String::new();
+        reader.read_line(&mut interim).unwrap();
+        assert_eq!(interim, "HTTP/1.1 100 Continue\r\n");
+        reader.read_line(&mut interim).unwrap();
+
+        stream.write_all(b"hello").unwrap();
+        let (status, headers, body) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(header(&headers, "connection"), Some("keep-alive"));
+        assert_eq!(body, b"5");
+    }
+
+    #[test]
+    fn test_no_continue_once_response_has_started() {
+        let handler = |mut req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            let _ = res.write_chunk(b"a");
+            req.body().bytes()?;
+            Ok(())
+        };
+        let server = Server::start(0, handler).expect("Failed to start server");
+        let mut stream = connect(&server);
+
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!response.contains("100 Continue"), "Interim response spliced into the body: {:?}", response);
+        assert!(response.ends_with("\r\n\r\n1\r\na\r\n"), "Response should be cut off: {:?}", response);
+    }
+
+    #[test]
+    fn test_no_continue_when_handler_ignores_body() {
+        let server = Server::start(0, echo_path).expect("Failed to start server");
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        stream.write_all(b"POST /skip HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n").unwrap();
+        let (status, headers, body) = read_response(&mut reader);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(header(&headers, "connection"), Some("close"));
+        assert_eq!(body, b"/skip");
+    }
+
+    #[test]
+    fn test_continue_check_rejects_without_reading_body() {
+        let config = Config::default().with_continue_check(|req: &Request| {
+            match req.header("Content-Length").and_then(|v| v.parse::<u64>().ok()) {
+                Some(len) if len > 10 => Err(HandlerError {
+                    status: StatusCode::ContentTooLarge,
+                    message: "Too big\n".to_string(),
+                }),
+                _ => Ok(()),
+            }
+        });
+        let server = Server::start_with_config(0, config, body_len).expect("Failed to start server");
+
+        let mut stream = connect(&server);
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 1000\r\n\r\n").unwrap();
+        let (status, headers, _) = read_response(&mut BufReader::new(stream));
+        assert_eq!(status, "HTTP/1.1 413 Content Too Large");
+        assert_eq!(header(&headers, "connection"), Some("close"));
+
+        let mut stream = connect(&server);
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 2\r\n\r\n").unwrap();
+        let mut interim = String::new();
+        reader.read_line(&mut interim).unwrap();
+        assert_eq!(interim, "HTTP/1.1 100 Continue\r\n");
+    }
+
+    #[test]
+    fn test_unknown_expectation_is_417() {
+        let server = Server::start(0, body_len).expect("Failed to start server");
+        let mut stream = connect(&server);
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nExpect: teapot\r\nContent-Length: 5\r\n\r\n").unwrap();
+        let (status, _, _) = read_response(&mut BufReader::new(stream));
+        assert_eq!(status, "HTTP/1.1 417 Expectation Failed");
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{self, Read};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+/// Read half of a connection that fails with `TimedOut` once its deadline
+/// passes. Unlike a plain socket read timeout, the deadline covers the whole
+/// phase, so a client trickling one byte at a time cannot stretch it.
+pub struct TimedStream {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl TimedStream {
+    pub fn new(stream: TcpStream) -> Self {
+        TimedStream { stream, deadline: None }
+    }
+
+    /// Gives the following reads `timeout` in total, or no limit for `None`.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.deadline = timeout.map(|t| Instant::now() + t);
+    }
+}
+
+impl Read for TimedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "Read deadline expired"));
+                }
+                Some(remaining)
+            }
+            None => None,
+        };
+        self.stream.set_read_timeout(remaining)?;
+
+        match self.stream.read(buf) {
+            // Timed out socket reads surface as WouldBlock on Unix.
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "Read deadline expired"))
+            }
+            result => result,
+        }
+    }
+}
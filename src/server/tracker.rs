@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Outcome of `Server::shutdown`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Connections that finished their in-flight request before the deadline.
+    pub drained: usize,
+    /// Keep-alive connections that were waiting for a request and got closed.
+    pub idle_closed: usize,
+    /// Connections still busy at the deadline whose sockets were shut down.
+    pub forced: usize,
+}
+
+struct Entry {
+    stream: TcpStream,
+    busy: bool,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    draining: bool,
+    conns: HashMap<u64, Entry>,
+}
+
+/// Keeps a handle to every open connection so shutdown can tell idle
+/// keep-alive sockets from ones with a request in flight.
+#[derive(Default)]
+pub struct ConnectionTracker {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// Registration of one connection; dropping it removes the connection.
+pub struct Tracked {
+    id: u64,
+    tracker: Arc<ConnectionTracker>,
+}
+
+impl ConnectionTracker {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts tracking `stream`, or returns `None` once draining has begun.
+    pub fn register(tracker: &Arc<Self>, stream: &TcpStream) -> Option<Tracked> {
+        let stream = stream.try_clone().ok()?;
+        let mut state = tracker.lock();
+        if state.draining {
+            return None;
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.conns.insert(id, Entry { stream, busy: false });
+        Some(Tracked { id, tracker: tracker.clone() })
+    }
+
+    /// Stops new work, closes idle connections and waits up to `timeout` for
+    /// busy ones before shutting their sockets down.
+    pub fn drain(&self, timeout: Duration) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        let mut state = self.lock();
+        state.draining = true;
+
+        let mut busy = 0;
+        for entry in state.conns.values() {
+            if entry.busy {
+                busy += 1;
+            } else {
+                let _ = entry.stream.shutdown(Shutdown::Both);
+                report.idle_closed += 1;
+            }
+        }
+
+        let deadline = Instant::now() + timeout;
+        while state.conns.values().any(|e| e.busy) {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        for entry in state.conns.values().filter(|e| e.busy) {
+            let _ = entry.stream.shutdown(Shutdown::Both);
+            report.forced += 1;
+        }
+        report.drained = busy - report.forced;
+        report
+    }
+}
+
+impl Tracked {
+    /// Marks the connection as serving a request. Returns `false` if the
+    /// server is draining and the request should not be started.
+    pub fn set_busy(&self) -> bool {
+        self.set(true)
+    }
+
+    /// Marks the connection as waiting for its next request. Returns `false`
+    /// if the server is draining and the connection should be closed.
+    pub fn set_idle(&self) -> bool {
+        self.set(false)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.tracker.lock().draining
+    }
+
+    fn set(&self, busy: bool) -> bool {
+        let mut state = self.tracker.lock();
+        if state.draining {
+            return false;
+        }
+        if let Some(entry) = state.conns.get_mut(&self.id) {
+            entry.busy = busy;
+        }
+        true
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.tracker.lock().conns.remove(&self.id);
+        self.tracker.changed.notify_all();
+    }
+}
@@ -0,0 +1,227 @@
+mod store;
+mod test;
+
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::cookie::{Cookie, Keys, SameSite};
+use crate::crypto;
+use crate::middleware::{Middleware, Next};
+use crate::request::Request;
+use crate::response::{HandlerError, StatusCode, Writer};
+
+pub use store::{FileStore, MemoryStore, SessionData, SessionStore};
+
+#[derive(Debug, Default)]
+struct State {
+    /// Id of the stored session this one was loaded from.
+    id: Option<String>,
+    data: SessionData,
+    changed: bool,
+    destroyed: bool,
+    renew: bool,
+}
+
+/// The session of the current request, set up by `SessionLayer` and reached
+/// through `Request::session`. Changes are saved and the cookie is sent
+/// once the handler returns successfully; a session that was never changed
+/// costs the client no cookie. Clones share the same session.
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    state: Arc<Mutex<State>>,
+}
+
+impl Session {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.state().data.get(key).cloned()
+    }
+
+    /// Parses the value of `key`, giving `None` when it is missing or does
+    /// not parse.
+    pub fn get_as<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.state().data.get(key).and_then(|v| v.parse().ok())
+    }
+
+    pub fn set(&self, key: &str, value: impl ToString) {
+        let mut state = self.state();
+        state.data.insert(key.to_string(), value.to_string());
+        state.changed = true;
+    }
+
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut state = self.state();
+        let old = state.data.remove(key);
+        state.changed |= old.is_some();
+        old
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.changed |= !state.data.is_empty();
+        state.data.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().data.is_empty()
+    }
+
+    /// Deletes the session from the store and the client.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.destroyed = true;
+    }
+
+    /// Moves the data to a new session id, as should be done when a user
+    /// logs in so an id planted before then becomes useless.
+    pub fn renew(&self) {
+        let mut state = self.state();
+        state.renew = true;
+        state.changed = true;
+    }
+}
+
+/// Middleware giving each request a `Session`. The session id travels in a
+/// cookie signed with `Keys`; the data stays in the `SessionStore`.
+pub struct SessionLayer {
+    store: Arc<dyn SessionStore>,
+    keys: Keys,
+    cookie_name: String,
+    path: String,
+    ttl: Duration,
+    secure: bool,
+    same_site: SameSite,
+}
+
+impl SessionLayer {
+    /// A layer with a `session` cookie for path `/` that lives 24 hours,
+    /// `HttpOnly` and `SameSite=Lax`.
+    pub fn new(store: impl SessionStore, keys: Keys) -> Self {
+        SessionLayer {
+            store: Arc::new(store),
+            keys,
+            cookie_name: "session".to_string(),
+            path: "/".to_string(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            secure: false,
+            same_site: SameSite::Lax,
+        }
+    }
+
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// How long a session lives after it was last changed.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Marks the cookie `Secure`; turn it on when serving over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    fn load(&self, req: &Request) -> Result<Session, HandlerError> {
+        let mut state = State::default();
+        if let Some(id) = req.signed_cookie(&self.keys, &self.cookie_name)
+            && let Some(data) = self.store.load(&id).map_err(store_error)?
+        {
+            state.id = Some(id);
+            state.data = data;
+        }
+        Ok(Session { state: Arc::new(Mutex::new(state)) })
+    }
+
+    /// Saves or removes the session and sends the cookie. Once the headers
+    /// have been sent (a streamed response) the cookie can no longer be set,
+    /// so only changes to an existing session are kept.
+    fn commit(&self, session: &Session, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        let mut state = session.state();
+        let can_set_cookie = !res.is_finished();
+
+        if state.destroyed {
+            if let Some(id) = state.id.take() {
+                self.store.remove(&id).map_err(store_error)?;
+                if can_set_cookie {
+                    res.set_cookie(&Cookie::removal(&self.cookie_name).path(&self.path));
+                }
+            }
+            return Ok(());
+        }
+        if !state.changed {
+            return Ok(());
+        }
+
+        if state.renew
+            && can_set_cookie
+            && let Some(old) = state.id.take()
+        {
+            self.store.remove(&old).map_err(store_error)?;
+        }
+        let id = match &state.id {
+            Some(id) => id.clone(),
+            None if can_set_cookie => new_id().map_err(store_error)?,
+            None => return Ok(()),
+        };
+
+        self.store.save(&id, &state.data, self.ttl).map_err(store_error)?;
+        if can_set_cookie {
+            let cookie = self
+                .keys
+                .sign(&self.cookie_name, &id)
+                .path(&self.path)
+                .max_age(self.ttl)
+                .http_only(true)
+                .secure(self.secure)
+                .same_site(self.same_site);
+            res.set_cookie(&cookie);
+        }
+        state.id = Some(id);
+        state.changed = false;
+        state.renew = false;
+        Ok(())
+    }
+}
+
+impl Middleware for SessionLayer {
+    fn handle(&self, mut req: Request, res: &mut Writer<TcpStream>, next: Next<'_>) -> Result<(), HandlerError> {
+        let session = self.load(&req)?;
+        req.extensions_mut().insert(session.clone());
+        next.run(req, res)?;
+        self.commit(&session, res)
+    }
+}
+
+/// A new session id: 32 random bytes, base64url encoded.
+fn new_id() -> std::io::Result<String> {
+    let mut bytes = [0u8; 32];
+    crypto::random_bytes(&mut bytes)?;
+    Ok(crypto::base64_encode(&bytes))
+}
+
+fn store_error(e: std::io::Error) -> HandlerError {
+    HandlerError {
+        status: StatusCode::InternalServerError,
+        message: format!("Session store failed: {}\n", e),
+    }
+}
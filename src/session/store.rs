@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::crypto;
+
+/// The values of one session.
+pub type SessionData = BTreeMap<String, String>;
+
+/// Where sessions are kept between requests. Ids are generated by the
+/// session layer from the characters `A-Z a-z 0-9 - _`.
+pub trait SessionStore: Send + Sync + 'static {
+    /// The data of session `id`, or `None` if it does not exist or expired.
+    fn load(&self, id: &str) -> io::Result<Option<SessionData>>;
+    /// Stores the data of session `id`, to expire after `ttl`.
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> io::Result<()>;
+    fn remove(&self, id: &str) -> io::Result<()>;
+}
+
+/// How many saves pass between sweeps for expired sessions.
+const SWEEP_INTERVAL: usize = 64;
+
+/// Keeps sessions in memory, so they are lost on restart. Expired sessions
+/// are dropped when loaded and swept out every few saves.
+#[derive(Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, (SessionData, Instant)>>,
+    saves: AtomicUsize,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every expired session.
+    pub fn evict_expired(&self) {
+        let now = Instant::now();
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).retain(|_, (_, expires)| *expires > now);
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionData>> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        match sessions.get(id) {
+            Some((_, expires)) if *expires <= Instant::now() => {
+                sessions.remove(id);
+                Ok(None)
+            }
+            Some((data, _)) => Ok(Some(data.clone())),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> io::Result<()> {
+        if self.saves.fetch_add(1, Ordering::Relaxed) % SWEEP_INTERVAL == SWEEP_INTERVAL - 1 {
+            self.evict_expired();
+        }
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string(), (data.clone(), Instant::now() + ttl));
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+        Ok(())
+    }
+}
+
+/// Keeps each session in a file of its own under a directory, so sessions
+/// survive restarts. Files are replaced atomically by renaming.
+pub struct FileStore {
+    dir: PathBuf,
+    saves: AtomicUsize,
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> io::Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            _ => return Err(invalid_data("Invalid escape in session file")),
+        }
+    }
+    Ok(out)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl FileStore {
+    /// Uses `dir` for session files, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileStore { dir, saves: AtomicUsize::new(0) })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.session", id))
+    }
+
+    /// The session in `contents` and whether it is still live. The first
+    /// line holds the expiry in Unix seconds, each further line a tab
+    /// separated name and value with `\`, tab and line breaks escaped.
+    fn decode(contents: &str) -> io::Result<(SessionData, bool)> {
+        let mut lines = contents.lines();
+        let expires: u64 = lines
+            .next()
+            .and_then(|l| l.parse().ok())
+            .ok_or_else(|| invalid_data("Missing expiry in session file"))?;
+
+        let mut data = SessionData::new();
+        for line in lines {
+            let (name, value) = line
+                .split_once('\t')
+                .ok_or_else(|| invalid_data("Invalid line in session file"))?;
+            data.insert(unescape(name)?, unescape(value)?);
+        }
+        Ok((data, expires > unix_now()))
+    }
+
+    /// Deletes the files of expired sessions.
+    pub fn evict_expired(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "session") {
+                continue;
+            }
+            let expired = match fs::read_to_string(&path) {
+                Ok(contents) => Self::decode(&contents).map(|(_, live)| !live).unwrap_or(true),
+                Err(_) => continue,
+            };
+            if expired {
+                let _ = fs::remove_file(&path);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionData>> {
+        if !valid_id(id) {
+            return Ok(None);
+        }
+        let contents = match fs::read_to_string(self.path(id)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let (data, live) = Self::decode(&contents)?;
+        if !live {
+            self.remove(id)?;
+            return Ok(None);
+        }
+        Ok(Some(data))
+    }
+
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> io::Result<()> {
+        if !valid_id(id) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid session id"));
+        }
+        // A failed sweep is no reason to lose this session's changes.
+        if self.saves.fetch_add(1, Ordering::Relaxed) % SWEEP_INTERVAL == SWEEP_INTERVAL - 1
+            && let Err(e) = self.evict_expired()
+        {
+            eprintln!("Failed to sweep expired sessions: {}", e);
+        }
+
+        let mut contents = format!("{}\n", unix_now() + ttl.as_secs());
+        for (name, value) in data {
+            contents.push_str(&format!("{}\t{}\n", escape(name), escape(value)));
+        }
+
+        let mut suffix = [0u8; 6];
+        crypto::random_bytes(&mut suffix)?;
+        let tmp = self.dir.join(format!("{}.{}.tmp", id, crypto::base64_encode(&suffix)));
+        // Session data is for this server alone, not other local users.
+        let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&tmp)?;
+        file.write_all(contents.as_bytes())
+            .and_then(|_| fs::rename(&tmp, self.path(id)))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmp);
+            })
+    }
+
+    fn remove(&self, id: &str) -> io::Result<()> {
+        if !valid_id(id) {
+            return Ok(());
+        }
+        match fs::remove_file(self.path(id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::net::TcpStream;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::cookie::{Key, Keys};
+    use crate::middleware::Chain;
+    use crate::request::Request;
+    use crate::response::{HandlerError, StatusCode, Writer};
+    use crate::server::Server;
+    use crate::server::test::roundtrip;
+    use crate::session::{FileStore, MemoryStore, SessionData, SessionLayer, SessionStore};
+
+    fn data(pairs: &[(&str, &str)]) -> SessionData {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("r_http-sessions-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_memory_store_ttl() {
+        let store = MemoryStore::new();
+        store.save("live", &data(&[("a", "1")]), Duration::from_secs(60)).unwrap();
+        store.save("stale", &data(&[("b", "2")]), Duration::ZERO).unwrap();
+
+        assert_eq!(store.load("live").unwrap(), Some(data(&[("a", "1")])));
+        assert_eq!(store.load("stale").unwrap(), None);
+        assert_eq!(store.load("missing").unwrap(), None);
+
+        store.save("stale", &data(&[]), Duration::ZERO).unwrap();
+        assert_eq!(store.len(), 2);
+        store.evict_expired();
+        assert_eq!(store.len(), 1);
+
+        store.remove("live").unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = temp_dir("file-store");
+        let store = FileStore::new(&dir).unwrap();
+
+        let tricky = data(&[("note", "line one\nline two\ttab \\ slash"), ("user", "alice")]);
+        store.save("abc-123_X", &tricky, Duration::from_secs(60)).unwrap();
+        assert_eq!(store.load("abc-123_X").unwrap(), Some(tricky));
+        let mode = dir.join("abc-123_X.session").metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600, "Session files must be private to the owner");
+
+        store.save("old", &data(&[("a", "1")]), Duration::ZERO).unwrap();
+        assert_eq!(store.load("old").unwrap(), None);
+        assert!(!dir.join("old.session").exists(), "Expired session file should be deleted");
+
+        assert_eq!(store.load("../escape").unwrap(), None);
+        assert!(store.save("../escape", &data(&[]), Duration::from_secs(60)).is_err());
+
+        store.save("gone", &data(&[("a", "1")]), Duration::ZERO).unwrap();
+        store.evict_expired().unwrap();
+        assert!(!dir.join("gone.session").exists());
+
+        store.remove("abc-123_X").unwrap();
+        store.remove("abc-123_X").unwrap();
+        assert_eq!(store.load("abc-123_X").unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Counts visits in the session; `/logout` destroys it and `/peek`
+    /// only reads it.
+    fn visits(req: Request, res: &mut Writer<TcpStream>) -> Result<(), HandlerError> {
+        let session = req.session().expect("Session layer should be installed");
+        match req.path() {
+            "/logout" => session.destroy(),
+            "/peek" => {}
+            _ => session.set("visits", session.get_as::<u32>("visits").unwrap_or(0) + 1),
+        }
+        res.set_status(StatusCode::Ok);
+        let _ = res.write_body(session.get("visits").unwrap_or_default().as_bytes());
+        Ok(())
+    }
+
+    fn get(server: &Server, path: &str, cookie: Option<&str>) -> String {
+        let cookie = cookie.map(|c| format!("Cookie: {}\r\n", c)).unwrap_or_default();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, cookie);
+        roundtrip(server, request.as_bytes())
+    }
+
+    fn set_cookie(response: &str) -> Option<&str> {
+        response.lines().find_map(|l| l.strip_prefix("Set-Cookie: "))
+    }
+
+    fn cookie_pair(set_cookie: &str) -> &str {
+        set_cookie.split(';').next().unwrap()
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let keys = Keys::new(Key::from_secret(&[7; 32]));
+        let layer = SessionLayer::new(MemoryStore::new(), keys).ttl(Duration::from_secs(600));
+        let server = Server::start(0, Chain::new(visits).layer(layer)).expect("Failed to start server");
+
+        let res = get(&server, "/peek", None);
+        assert!(set_cookie(&res).is_none(), "Untouched sessions send no cookie");
+
+        let res = get(&server, "/", None);
+        assert!(res.ends_with("\r\n\r\n1"));
+        let first = set_cookie(&res).expect("New session should set a cookie").to_string();
+        assert!(first.starts_with("session="));
+        assert!(first.contains("; Max-Age=600; Path=/; HttpOnly; SameSite=Lax"));
+        let cookie = cookie_pair(&first);
+
+        let res = get(&server, "/", Some(cookie));
+        assert!(res.ends_with("\r\n\r\n2"));
+        assert_eq!(cookie_pair(set_cookie(&res).unwrap()), cookie, "Session id is kept");
+
+        let res = get(&server, "/peek", Some(cookie));
+        assert!(res.ends_with("\r\n\r\n2"));
+        assert!(set_cookie(&res).is_none());
+
+        // A tampered id is ignored and a fresh session started.
+        let forged = cookie.replacen("session=", "session=x", 1);
+        let res = get(&server, "/", Some(&forged));
+        assert!(res.ends_with("\r\n\r\n1"));
+        assert_ne!(cookie_pair(set_cookie(&res).unwrap()), cookie);
+
+        let res = get(&server, "/logout", Some(cookie));
+        let removal = set_cookie(&res).unwrap();
+        assert!(removal.starts_with("session=; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0"));
+
+        let res = get(&server, "/", Some(cookie));
+        assert!(res.ends_with("\r\n\r\n1"), "Destroyed session should be gone");
+    }
+
+    #[test]
+    fn test_renew_moves_data_to_new_id() {
+        let login = |req: Request, res: &mut Writer<TcpStream>| -> Result<(), HandlerError> {
+            let session = req.session().unwrap();
+            if req.path() == "/login" {
+                session.renew();
+                session.set("user", "alice");
+            } else {
+                session.set("cart", "3 items");
+            }
+            res.set_status(StatusCode::Ok);
+            Ok(())
+        };
+        let keys = Keys::new(Key::from_secret(&[8; 32]));
+        let server = Server::start(0, Chain::new(login).layer(SessionLayer::new(MemoryStore::new(), keys)))
+            .expect("Failed to start server");
+
+        let res = get(&server, "/cart", None);
+        let before = cookie_pair(set_cookie(&res).unwrap()).to_string();
+        let res = get(&server, "/login", Some(&before));
+        let after = cookie_pair(set_cookie(&res).unwrap()).to_string();
+        assert_ne!(before, after);
+
+        // The old id no longer finds the session.
+        let res = get(&server, "/cart", Some(&before));
+        assert_ne!(cookie_pair(set_cookie(&res).unwrap()), before);
+    }
+}
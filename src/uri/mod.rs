@@ -0,0 +1,270 @@
+mod test;
+
+use crate::request::ParseError;
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// A parsed request target (RFC 9112 section 3.2). The path is normalized:
+/// `.` and `..` segments are resolved, and a `..` that would climb above the
+/// root is refused. Encoded slashes and NULs are refused too, so a decoded
+/// segment can never be mistaken for two or cut a file name short.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Uri {
+    scheme: Option<String>,
+    authority: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    path: String,
+    segments: Vec<String>,
+    query: Option<String>,
+}
+
+impl Default for Uri {
+    fn default() -> Self {
+        Uri {
+            scheme: None,
+            authority: None,
+            host: None,
+            port: None,
+            path: "/".to_string(),
+            segments: Vec::new(),
+            query: None,
+        }
+    }
+}
+
+impl Uri {
+    /// Parses an absolute URI (`http://host/path?query`), an origin-form
+    /// target (`/path?query`) or the asterisk form `*`.
+    pub fn parse(target: &str) -> Result<Uri> {
+        if target.contains('#') {
+            return Err(ParseError::InvalidUri("Fragment is not allowed in a request target"));
+        }
+        if target == "*" {
+            return Ok(Uri { path: "*".to_string(), ..Uri::default() });
+        }
+
+        let mut uri = Uri::default();
+        let rest = match target.split_once("://") {
+            Some((scheme, rest)) => {
+                if scheme.is_empty()
+                    || !scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                    || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                {
+                    return Err(ParseError::InvalidUri("Invalid scheme"));
+                }
+                let end = rest.find(['/', '?']).unwrap_or(rest.len());
+                let (host, port) = Self::parse_authority(&rest[..end])?;
+                uri.scheme = Some(scheme.to_ascii_lowercase());
+                uri.authority = Some(rest[..end].to_string());
+                uri.host = Some(host);
+                uri.port = port;
+                &rest[end..]
+            }
+            None if target.starts_with('/') => target,
+            None => return Err(ParseError::InvalidTarget),
+        };
+
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+        if let Some(query) = query {
+            check_query(query)?;
+            uri.query = Some(query.to_string());
+        }
+        if !path.is_empty() {
+            uri.normalize_path(path)?;
+        }
+        Ok(uri)
+    }
+
+    /// Splits `host[:port]` into a lowercased host and the port, if any.
+    /// User info is refused as RFC 9110 section 4.2.4 asks.
+    pub(crate) fn parse_authority(authority: &str) -> Result<(String, Option<u16>)> {
+        if authority.contains('@') {
+            return Err(ParseError::InvalidUri("User info is not allowed in an http URI"));
+        }
+
+        let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+            let (literal, after) = rest
+                .split_once(']')
+                .ok_or(ParseError::InvalidUri("Unterminated IP literal"))?;
+            if literal.is_empty() || !literal.chars().all(|c| c.is_ascii_hexdigit() || matches!(c, ':' | '.')) {
+                return Err(ParseError::InvalidUri("Invalid IP literal"));
+            }
+            match after {
+                "" => (&authority[..literal.len() + 2], None),
+                _ => match after.strip_prefix(':') {
+                    Some(port) => (&authority[..literal.len() + 2], Some(port)),
+                    None => return Err(ParseError::InvalidUri("Invalid authority")),
+                },
+            }
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            }
+        };
+
+        if host.is_empty() {
+            return Err(ParseError::InvalidUri("Missing host"));
+        }
+        if !host.starts_with('[')
+            && !host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~' | '%'))
+        {
+            return Err(ParseError::InvalidUri("Invalid host"));
+        }
+
+        // An empty port is allowed and means the scheme's default.
+        let port = match port {
+            None | Some("") => None,
+            Some(port) if port.bytes().all(|b| b.is_ascii_digit()) => {
+                Some(port.parse().map_err(|_| ParseError::InvalidUri("Port out of range"))?)
+            }
+            Some(_) => return Err(ParseError::InvalidUri("Invalid port")),
+        };
+        Ok((host.to_ascii_lowercase(), port))
+    }
+
+    fn normalize_path(&mut self, path: &str) -> Result<()> {
+        check_chars(path)?;
+
+        // Raw and decoded form of each segment kept so far.
+        let mut out: Vec<(&str, String)> = Vec::new();
+        let raw: Vec<&str> = path[1..].split('/').collect();
+        for (i, segment) in raw.iter().enumerate() {
+            let decoded = percent_decode(segment)?;
+            if decoded.contains(['/', '\0']) {
+                return Err(ParseError::InvalidUri("Encoded slash or NUL in path"));
+            }
+            match decoded.as_str() {
+                "." => {}
+                ".." => {
+                    if out.pop().is_none() {
+                        return Err(ParseError::InvalidUri("Path escapes the root"));
+                    }
+                }
+                _ => {
+                    out.push((segment, decoded));
+                    continue;
+                }
+            }
+            // A trailing dot segment still names a directory.
+            if i == raw.len() - 1 {
+                out.push(("", String::new()));
+            }
+        }
+
+        let raw: Vec<&str> = out.iter().map(|(raw, _)| *raw).collect();
+        self.path = format!("/{}", raw.join("/"));
+        self.segments = out.into_iter().map(|(_, decoded)| decoded).filter(|s| !s.is_empty()).collect();
+        Ok(())
+    }
+
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.as_deref()
+    }
+
+    /// The `host[:port]` part as it was received.
+    pub fn authority(&self) -> Option<&str> {
+        self.authority.as_deref()
+    }
+
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The normalized path, still percent-encoded.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The non-empty path segments, percent-decoded.
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().map(String::as_str)
+    }
+
+    /// The query string without the leading `?`, still percent-encoded.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+}
+
+/// Refuses characters that may not appear in a path, and `%` not followed
+/// by two hex digits.
+fn check_chars(s: &str) -> Result<()> {
+    let bytes = s.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        if !b.is_ascii_graphic() || matches!(b, b'"' | b'<' | b'>' | b'\\' | b'^' | b'`' | b'{' | b'|' | b'}') {
+            return Err(ParseError::InvalidUri("Invalid character in request target"));
+        }
+        if b == b'%' && !(bytes.get(i + 1..i + 3).is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit))) {
+            return Err(ParseError::InvalidUri("Malformed percent-encoding"));
+        }
+    }
+    Ok(())
+}
+
+/// Refuses controls, spaces, `#` and non-ASCII in a query. Browsers send
+/// characters like `|` and `{` there unencoded, so those are let through;
+/// malformed escapes only fail once the query is decoded.
+fn check_query(s: &str) -> Result<()> {
+    if s.bytes().all(|b| b.is_ascii_graphic() && b != b'#') {
+        Ok(())
+    } else {
+        Err(ParseError::InvalidUri("Invalid character in query"))
+    }
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Decodes `%XX` escapes, failing on malformed escapes or a result that is
+/// not UTF-8.
+pub fn percent_decode(s: &str) -> Result<String> {
+    decode(s, false)
+}
+
+/// Like `percent_decode`, but also turns `+` into a space as
+/// `application/x-www-form-urlencoded` does.
+pub fn form_decode(s: &str) -> Result<String> {
+    decode(s, true)
+}
+
+fn decode(s: &str, plus_as_space: bool) -> Result<String> {
+    if !(s.contains('%') || plus_as_space && s.contains('+')) {
+        return Ok(s.to_string());
+    }
+
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hi = bytes.get(i + 1).copied().and_then(hex_value);
+                let lo = bytes.get(i + 2).copied().and_then(hex_value);
+                let (Some(hi), Some(lo)) = (hi, lo) else {
+                    return Err(ParseError::InvalidUri("Malformed percent-encoding"));
+                };
+                out.push(hi << 4 | lo);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).map_err(|_| ParseError::InvalidUri("Percent-encoded bytes are not UTF-8"))
+}
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use crate::request::ParseError;
+    use crate::uri::{Uri, form_decode, percent_decode};
+
+    fn path_of(target: &str) -> String {
+        Uri::parse(target).unwrap().path().to_string()
+    }
+
+    fn is_invalid(target: &str) -> bool {
+        matches!(Uri::parse(target), Err(ParseError::InvalidUri(_)))
+    }
+
+    #[test]
+    fn test_absolute_uri_parts() {
+        let uri = Uri::parse("HTTP://Example.COM:8080/a/b?x=1&y=2").unwrap();
+        assert_eq!(uri.scheme(), Some("http"));
+        assert_eq!(uri.authority(), Some("Example.COM:8080"));
+        assert_eq!(uri.host(), Some("example.com"));
+        assert_eq!(uri.port(), Some(8080));
+        assert_eq!(uri.path(), "/a/b");
+        assert_eq!(uri.query(), Some("x=1&y=2"));
+    }
+
+    #[test]
+    fn test_origin_form_and_defaults() {
+        let uri = Uri::parse("/search?q=x").unwrap();
+        assert_eq!(uri.host(), None);
+        assert_eq!(uri.path(), "/search");
+        assert_eq!(uri.query(), Some("q=x"));
+
+        let uri = Uri::parse("http://localhost").unwrap();
+        assert_eq!(uri.path(), "/");
+        assert_eq!(uri.port(), None);
+        assert_eq!(Uri::parse("http://localhost:/").unwrap().port(), None);
+        assert_eq!(Uri::parse("*").unwrap().path(), "*");
+    }
+
+    #[test]
+    fn test_ip_literal_host() {
+        let uri = Uri::parse("http://[::1]:3000/").unwrap();
+        assert_eq!(uri.host(), Some("[::1]"));
+        assert_eq!(uri.port(), Some(3000));
+        assert!(is_invalid("http://[::1/"));
+    }
+
+    #[test]
+    fn test_invalid_authorities() {
+        assert!(is_invalid("http://user:pw@example.com/"));
+        assert!(is_invalid("http://example.com:99999/"));
+        assert!(is_invalid("http://example.com:80a/"));
+        assert!(is_invalid("http:///path"));
+    }
+
+    #[test]
+    fn test_fragment_rejected() {
+        assert!(is_invalid("/page#section"));
+    }
+
+    #[test]
+    fn test_query_allows_characters_browsers_leave_unencoded() {
+        for target in ["/s?q=a|b", "/s?q={x}", "/s?q=a^b`c\\d\"e<f>", "/s?q=100%"] {
+            let query = &target[3..];
+            assert_eq!(Uri::parse(target).unwrap().query(), Some(query), "{} should be accepted", target);
+        }
+        assert!(is_invalid("/s?q=a b"));
+        assert!(is_invalid("/s?q=a\tb"));
+        assert!(is_invalid("/s?q=caf\u{e9}"));
+        assert!(is_invalid("/s|t?q=x"), "The path keeps the strict check");
+    }
+
+    #[test]
+    fn test_segments_are_decoded() {
+        let uri = Uri::parse("/files/my%20doc/caf%C3%A9").unwrap();
+        assert_eq!(uri.path(), "/files/my%20doc/caf%C3%A9");
+        assert_eq!(uri.segments().collect::<Vec<_>>(), vec!["files", "my doc", "café"]);
+
+        assert!(is_invalid("/bad%2"));
+        assert!(is_invalid("/bad%zz"));
+        assert!(is_invalid("/bad%FF"));
+        assert!(is_invalid("/a%2Fb"));
+        assert!(is_invalid("/a%00"));
+    }
+
+    #[test]
+    fn test_dot_segments_removed() {
+        assert_eq!(path_of("/a/./b/../c"), "/a/c");
+        assert_eq!(path_of("/a/b/.."), "/a/");
+        assert_eq!(path_of("/a/."), "/a/");
+        assert_eq!(path_of("/a/%2e%2E/b"), "/b");
+        assert_eq!(path_of("/a//../b"), "/a/b");
+        assert_eq!(path_of("/"), "/");
+    }
+
+    #[test]
+    fn test_traversal_rejected() {
+        assert!(is_invalid("/../etc/passwd"));
+        assert!(is_invalid("/a/../../etc"));
+        assert!(is_invalid("/%2e%2e/etc"));
+        assert!(is_invalid("http://localhost/.."));
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b+c").unwrap(), "a b+c");
+        assert!(percent_decode("%4").is_err());
+        assert_eq!(form_decode("a%20b+c%2B").unwrap(), "a b c+");
+    }
+}